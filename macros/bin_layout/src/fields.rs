@@ -1,6 +1,6 @@
 use proc_macro2::TokenStream;
-use quote::{quote, quote_spanned, ToTokens};
-use syn::{Expr, Fields, Ident, Meta, Type};
+use quote::quote;
+use syn::{Expr, Fields, Ident, Meta};
 
 struct FieldMetadata {
     ident: Ident,
@@ -48,12 +48,6 @@ impl FieldMetadata {
             }
         }
     }
-    fn first_num(value: &Expr) -> usize {
-        let self_tokens = value.to_token_stream();
-        // get first number in token stream
-        let self_num = self_tokens.into_iter().next().unwrap();
-        self_num.to_string().parse().unwrap()
-    }
 }
 
 pub fn get_bounding_code(fields: Fields) -> TokenStream {
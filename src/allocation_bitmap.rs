@@ -0,0 +1,487 @@
+//! # Purpose
+//! Keeps track of which clusters of the Cluster Heap are in use through the
+//! Allocation Bitmap, and finds free runs of clusters to allocate.
+//!
+//! The whole bitmap is held in memory as 64 bit words, the low bit of the
+//! first word describing cluster 2, so that free clusters can be searched for
+//! a word at a time. Every change is written through to the disk.
+
+use core::fmt::Display;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    block_device::BlockDevice, cluster_heap::ClusterHeap,
+    dir_entry::AllocationBitmapEntry, fat::Fat, fat_entry::FatEntry, stream,
+};
+
+/// Ways an Allocation Bitmap can be unusable.
+#[derive(Debug)]
+pub enum Error {
+    /// A cluster outside of the Cluster Heap, which spans clusters 2 through
+    /// ClusterCount + 1, was asked for.
+    OutOfRange { cluster: u32, cluster_count: u32 },
+    /// The root directory has no Allocation Bitmap directory entry for the
+    /// active FAT.
+    Missing,
+    /// The DataLength of the Allocation Bitmap is too short to hold a bit for
+    /// every cluster of the Cluster Heap.
+    TooShort { data_length: u64, expected: u64 },
+    /// Fewer clusters are free than were asked for.
+    Full { requested: u32, free: u32 },
+    /// No run of free clusters is long enough to allocate the clusters asked
+    /// for contiguously, though enough may be free in total.
+    Fragmented { requested: u32 },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::OutOfRange {
+                cluster,
+                cluster_count,
+            } => write!(
+                f,
+                "Cluster {} is outside of the cluster heap, which spans \
+                clusters 2 through {}.",
+                cluster,
+                *cluster_count as u64 + 1
+            ),
+            Error::Missing => write!(
+                f,
+                "The root directory has no allocation bitmap for the active \
+                FAT."
+            ),
+            Error::TooShort {
+                data_length,
+                expected,
+            } => write!(
+                f,
+                "The allocation bitmap is {} bytes long rather than the {} \
+                bytes the cluster heap needs.",
+                data_length, expected
+            ),
+            Error::Full { requested, free } => write!(
+                f,
+                "Cannot allocate {} clusters when only {} are free.",
+                requested, free
+            ),
+            Error::Fragmented { requested } => write!(
+                f,
+                "Cannot allocate {} contiguous clusters as no run of free \
+                clusters is that long.",
+                requested
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The Allocation Bitmap of a volume.
+pub struct AllocationBitmap {
+    heap: ClusterHeap,
+    /// The clusters the bitmap is stored in, in order.
+    clusters: Vec<u32>,
+    words: Vec<u64>,
+    free_count: u32,
+}
+
+impl AllocationBitmap {
+    /// The number of bytes an Allocation Bitmap describing `cluster_count`
+    /// clusters takes up.
+    pub fn length(cluster_count: u32) -> u64 {
+        (cluster_count as u64).div_ceil(8)
+    }
+
+    /// An Allocation Bitmap with every cluster free, to be stored in the
+    /// `clusters` clusters following `first_cluster`.
+    pub fn new(heap: ClusterHeap, first_cluster: u32, clusters: u32) -> Self {
+        let cluster_count = heap.cluster_count();
+        Self {
+            heap,
+            clusters: (first_cluster..first_cluster + clusters).collect(),
+            words: vec![0; (cluster_count as usize).div_ceil(64)],
+            free_count: cluster_count,
+        }
+    }
+
+    /// Reads the Allocation Bitmap an [`AllocationBitmapEntry`] points to.
+    pub fn load(
+        heap: ClusterHeap,
+        fat: &Fat,
+        disk: &mut impl BlockDevice,
+        entry: &AllocationBitmapEntry,
+    ) -> Result<Self, crate::Error> {
+        let cluster_count = heap.cluster_count();
+        let expected = Self::length(cluster_count);
+        if entry.data_length < expected {
+            return Err(Error::TooShort {
+                data_length: entry.data_length,
+                expected,
+            }
+            .into());
+        }
+        let clusters = fat
+            .chain(disk, entry.first_cluster)
+            .take(heap.clusters_for(expected) as usize)
+            .collect::<Result<Vec<_>, _>>()?;
+        if (clusters.len() as u64) < heap.clusters_for(expected) {
+            return Err(stream::Error::TooShort {
+                data_length: expected,
+                clusters: clusters.len(),
+            }
+            .into());
+        }
+        let mut bytes = vec![0u8; expected as usize];
+        for (cluster, chunk) in clusters
+            .iter()
+            .zip(bytes.chunks_mut(heap.bytes_per_cluster() as usize))
+        {
+            disk.read_at(heap.cluster_offset(*cluster), chunk)?;
+        }
+        let mut words = vec![0; (cluster_count as usize).div_ceil(64)];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks(8)) {
+            let mut le = [0u8; 8];
+            le[..chunk.len()].copy_from_slice(chunk);
+            *word = u64::from_le_bytes(le);
+        }
+        // bits past the last cluster have no meaning
+        if let Some(last) = words.last_mut() {
+            let used = cluster_count % 64;
+            if used != 0 {
+                *last &= (1 << used) - 1;
+            }
+        }
+        let allocated: u32 = words.iter().map(|word| word.count_ones()).sum();
+        Ok(Self {
+            heap,
+            clusters,
+            words,
+            free_count: cluster_count - allocated,
+        })
+    }
+
+    /// The clusters the bitmap is stored in.
+    pub fn clusters(&self) -> &[u32] {
+        &self.clusters
+    }
+
+    /// The number of clusters of the Cluster Heap not in use.
+    pub fn free_count(&self) -> u32 {
+        self.free_count
+    }
+
+    fn index(&self, cluster: u32) -> Result<usize, Error> {
+        if self.heap.contains(cluster) {
+            Ok((cluster - FatEntry::FIRST_CLUSTER) as usize)
+        } else {
+            Err(Error::OutOfRange {
+                cluster,
+                cluster_count: self.heap.cluster_count(),
+            })
+        }
+    }
+
+    /// Whether `cluster` is in use.
+    pub fn is_allocated(&self, cluster: u32) -> Result<bool, Error> {
+        let index = self.index(cluster)?;
+        Ok(self.words[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// Marks the `count` clusters starting at `first_cluster` as in use or
+    /// free in memory only.
+    pub(crate) fn mark(
+        &mut self,
+        first_cluster: u32,
+        count: u32,
+        allocated: bool,
+    ) -> Result<(), Error> {
+        if count == 0 {
+            return Ok(());
+        }
+        let start = self.index(first_cluster)?;
+        let end = self.index(first_cluster.saturating_add(count - 1))? + 1;
+        for index in start..end {
+            let (word, bit) = (&mut self.words[index / 64], 1 << (index % 64));
+            if (*word & bit != 0) != allocated {
+                *word ^= bit;
+                if allocated {
+                    self.free_count -= 1;
+                } else {
+                    self.free_count += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the `count` clusters starting at `first_cluster` as in use or
+    /// free.
+    pub fn set(
+        &mut self,
+        disk: &mut impl BlockDevice,
+        first_cluster: u32,
+        count: u32,
+        allocated: bool,
+    ) -> Result<(), crate::Error> {
+        self.mark(first_cluster, count, allocated)?;
+        if count == 0 {
+            return Ok(());
+        }
+        let start = (first_cluster - FatEntry::FIRST_CLUSTER) as u64 / 8;
+        let end =
+            (first_cluster - FatEntry::FIRST_CLUSTER + (count - 1)) as u64 / 8
+                + 1;
+        self.write_bytes(disk, start, end)?;
+        disk.flush()
+    }
+
+    /// Writes the whole bitmap to the disk.
+    pub fn write(
+        &self,
+        disk: &mut impl BlockDevice,
+    ) -> Result<(), crate::Error> {
+        self.write_bytes(disk, 0, Self::length(self.heap.cluster_count()))?;
+        disk.flush()
+    }
+
+    /// Writes bytes `start..end` of the bitmap to the disk.
+    fn write_bytes(
+        &self,
+        disk: &mut impl BlockDevice,
+        start: u64,
+        end: u64,
+    ) -> Result<(), crate::Error> {
+        let bytes_per_cluster = self.heap.bytes_per_cluster();
+        let mut position = start;
+        while position < end {
+            let cluster =
+                self.clusters[(position / bytes_per_cluster) as usize];
+            let within = position % bytes_per_cluster;
+            let len = (bytes_per_cluster - within).min(end - position);
+            let bytes: Vec<u8> = (position..position + len)
+                .map(|byte| {
+                    (self.words[byte as usize / 8] >> (byte % 8 * 8)) as u8
+                })
+                .collect();
+            disk.write_at(self.heap.cluster_offset(cluster) + within, &bytes)?;
+            position += len;
+        }
+        Ok(())
+    }
+
+    /// Finds `count` free clusters in a row, preferring the first such run at
+    /// or after `hint` and otherwise wrapping around to the start of the
+    /// Cluster Heap. Returns the first cluster of the run.
+    pub fn find_free(&self, hint: u32, count: u32) -> Option<u32> {
+        if count == 0 || count > self.free_count {
+            return None;
+        }
+        let start = self.index(hint).unwrap_or(0);
+        self.find_free_in(start, count)
+            .or_else(|| self.find_free_in(0, count))
+            .map(|index| index as u32 + FatEntry::FIRST_CLUSTER)
+    }
+
+    /// Finds the first run of `count` free clusters at or after cluster
+    /// index `start`, skipping over whole words of clusters at a time.
+    fn find_free_in(&self, start: usize, count: u32) -> Option<usize> {
+        let end = self.heap.cluster_count() as usize;
+        let mut position = start;
+        let mut run_start = start;
+        let mut run_length = 0;
+        while position < end {
+            let within = position % 64;
+            let left = (64 - within).min(end - position) as u32;
+            let word = self.words[position / 64] >> within;
+            let free = word.trailing_zeros().min(left);
+            if free > 0 {
+                if run_length == 0 {
+                    run_start = position;
+                }
+                run_length += free;
+                if run_length >= count {
+                    return Some(run_start);
+                }
+                position += free as usize;
+            } else {
+                run_length = 0;
+                position += word.trailing_ones().min(left) as usize;
+            }
+        }
+        None
+    }
+
+    /// Whether the `count` clusters starting at `first_cluster` are all part
+    /// of the Cluster Heap and free.
+    pub fn is_free(&self, first_cluster: u32, count: u32) -> bool {
+        (0..count).all(|i| {
+            first_cluster.checked_add(i).is_some_and(|cluster| {
+                matches!(self.is_allocated(cluster), Ok(false))
+            })
+        })
+    }
+
+    /// Allocates `count` clusters, in one run if there is one and otherwise
+    /// in as few runs as the search finds, preferring clusters at or after
+    /// `hint`. Returns the clusters in the order they should be chained.
+    pub fn allocate(
+        &mut self,
+        disk: &mut impl BlockDevice,
+        hint: u32,
+        count: u32,
+    ) -> Result<Vec<u32>, crate::Error> {
+        if count > self.free_count {
+            return Err(Error::Full {
+                requested: count,
+                free: self.free_count,
+            }
+            .into());
+        }
+        if let Some(first) = self.find_free(hint, count) {
+            self.set(disk, first, count, true)?;
+            return Ok((first..first + count).collect());
+        }
+        let mut clusters = Vec::with_capacity(count as usize);
+        let mut hint = hint;
+        while clusters.len() < count as usize {
+            let remaining = count - clusters.len() as u32;
+            let first = self
+                .find_free(hint, 1)
+                .expect("the free count covers every remaining cluster");
+            let run = (first..first.saturating_add(remaining))
+                .take_while(|cluster| self.is_free(*cluster, 1))
+                .count() as u32;
+            self.set(disk, first, run, true)?;
+            clusters.extend(first..first + run);
+            hint = first + run;
+        }
+        Ok(clusters)
+    }
+
+    /// The directory entry recording where the bitmap is.
+    pub fn entry(&self, bitmap_identifier: u8) -> AllocationBitmapEntry {
+        AllocationBitmapEntry::new(
+            bitmap_identifier,
+            self.clusters[0],
+            Self::length(self.heap.cluster_count()),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        fat_entry::Link,
+        format_options::FormatOptions,
+        root_directory::RootEntries,
+        shift::ShiftedSectors,
+        stream,
+        test_util::{format_with, DISK_SIZE},
+        Error, FileSystem,
+    };
+
+    use super::AllocationBitmap;
+
+    #[test]
+    pub fn allocate_and_search() {
+        let fs = format_with(
+            vec![0xAA; DISK_SIZE],
+            ShiftedSectors::from(0),
+            &FormatOptions::default(),
+        );
+        let bitmap = fs.allocation_bitmap();
+        let cluster_count = fs.fat().cluster_count();
+        // the bitmap, the up-case table and the root directory are all that
+        // is allocated
+        let used = fs
+            .boot_region()
+            .boot_sector()
+            .first_cluster_of_root_directory()
+            - 1;
+        assert_eq!(bitmap.free_count(), cluster_count - used);
+        for cluster in 2..2 + used {
+            assert!(bitmap.is_allocated(cluster).unwrap());
+        }
+        assert!(!bitmap.is_allocated(2 + used).unwrap());
+        assert!(!bitmap.is_allocated(cluster_count + 1).unwrap());
+        assert!(bitmap.is_allocated(cluster_count + 2).is_err());
+        assert_eq!(bitmap.find_free(2, 1), Some(2 + used));
+
+        // the bitmap survives being mounted again
+        let mut disk = fs.unmount();
+        let mut fs = FileSystem::mount(&mut disk).unwrap();
+        let fat = *fs.fat();
+        let heap = fs.cluster_heap();
+        let entry = fs.allocation_bitmap().entry(0);
+        let disk = fs.boot_region_mut().disk_mut();
+        let mut bitmap =
+            AllocationBitmap::load(heap, &fat, disk, &entry).unwrap();
+        assert_eq!(bitmap.free_count(), cluster_count - used);
+
+        // leave a gap of 63 clusters straddling a word boundary
+        let gap = 100;
+        bitmap.set(disk, 2 + used, gap - used - 2, true).unwrap();
+        bitmap.set(disk, gap + 63, 200, true).unwrap();
+        assert_eq!(bitmap.find_free(2, 63), Some(gap));
+        assert_eq!(bitmap.find_free(2, 64), Some(gap + 263));
+        assert_eq!(bitmap.find_free(gap + 1, 1), Some(gap + 1));
+        // wraps around to the start of the heap
+        bitmap
+            .set(disk, gap + 263, cluster_count + 2 - gap - 263, true)
+            .unwrap();
+        assert_eq!(bitmap.find_free(gap + 263, 2), Some(gap));
+        assert_eq!(bitmap.find_free(2, 64), None);
+        assert_eq!(bitmap.free_count(), 63);
+
+        bitmap.set(disk, gap, 63, true).unwrap();
+        assert_eq!(bitmap.free_count(), 0);
+        assert_eq!(bitmap.find_free(2, 1), None);
+        bitmap.set(disk, cluster_count + 1, 1, false).unwrap();
+        assert_eq!(bitmap.find_free(2, 1), Some(cluster_count + 1));
+        assert!(bitmap.set(disk, cluster_count + 1, 2, true).is_err());
+
+        let loaded = AllocationBitmap::load(heap, &fat, disk, &entry).unwrap();
+        assert_eq!(loaded.words, bitmap.words);
+        assert_eq!(loaded.free_count(), 1);
+
+        // a chain cut short cannot hold the whole bitmap
+        fat.set_entry(disk, entry.first_cluster, Link::EndOfChain)
+            .unwrap();
+        assert!(matches!(
+            AllocationBitmap::load(heap, &fat, disk, &entry),
+            Err(Error::Stream(stream::Error::TooShort { clusters: 1, .. }))
+        ));
+    }
+
+    #[test]
+    pub fn one_bitmap_per_fat() {
+        let options = FormatOptions {
+            number_of_fats: 2,
+            ..FormatOptions::default()
+        };
+        let fs =
+            format_with(vec![0; DISK_SIZE], ShiftedSectors::from(0), &options);
+        let bitmap_clusters = fs.allocation_bitmap().clusters().len() as u32;
+        let root = fs
+            .boot_region()
+            .boot_sector()
+            .first_cluster_of_root_directory();
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        let (heap, fat) = (fs.cluster_heap(), *fs.fat());
+        let disk = fs.boot_region_mut().disk_mut();
+        let entries = RootEntries::read(heap, &fat, disk, root).unwrap();
+        let first_clusters: Vec<_> = entries
+            .allocation_bitmaps
+            .iter()
+            .map(|entry| (entry.bitmap_identifier(), entry.first_cluster))
+            .collect();
+        assert_eq!(first_clusters, [(0, 2), (1, 2 + bitmap_clusters)]);
+        assert_eq!(fs.allocation_bitmap().clusters()[0], 2);
+        assert_eq!(
+            fs.free_cluster_count(),
+            fs.fat().cluster_count() - (root - 1)
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! # Purpose
+//! Defines [`BlockDevice`], the sector-granular storage the file system is
+//! built on, and, with the `std` feature, `IoDevice` which adapts anything
+//! implementing `Read` + `Write` + `Seek` into a [`BlockDevice`].
+
+use alloc::vec;
+#[cfg(feature = "std")]
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use crate::{error::Error, shift::BytesPerSector};
+
+/// Storage which can only be read and written in whole sectors, such as an
+/// SD card, an eMMC chip or a raw block device.
+///
+/// The sector size of the device need not match the sector size of the
+/// volume stored on it; [`BlockDevice::read_at`] and
+/// [`BlockDevice::write_at`] translate byte ranges into whole sector
+/// transfers.
+pub trait BlockDevice {
+    /// The number of bytes in one of the device's sectors.
+    fn sector_size(&self) -> BytesPerSector;
+
+    /// The number of sectors the device holds.
+    fn sector_count(&self) -> u64;
+
+    /// Fills `buf`, whose length is a multiple of the sector size, with the
+    /// sectors starting at `first_sector`.
+    fn read_sectors(
+        &mut self,
+        first_sector: u64,
+        buf: &mut [u8],
+    ) -> Result<(), Error>;
+
+    /// Writes `buf`, whose length is a multiple of the sector size, to the
+    /// sectors starting at `first_sector`.
+    fn write_sectors(
+        &mut self,
+        first_sector: u64,
+        buf: &[u8],
+    ) -> Result<(), Error>;
+
+    /// Makes sure every write so far has reached the underlying storage.
+    fn flush(&mut self) -> Result<(), Error>;
+
+    /// The number of bytes the device holds.
+    fn len(&self) -> u64 {
+        self.sector_count() * *self.sector_size() as u64
+    }
+
+    fn is_empty(&self) -> bool {
+        self.sector_count() == 0
+    }
+
+    /// Fills `buf` with the bytes starting at byte `offset`, reading whole
+    /// sectors from the device.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<(), Error> {
+        let sector_size = *self.sector_size();
+        let head = (offset % sector_size as u64) as usize;
+        let first_sector = offset / sector_size as u64;
+        if head == 0 && buf.len().is_multiple_of(sector_size) {
+            return self.read_sectors(first_sector, buf);
+        }
+        let mut sectors =
+            vec![0u8; (head + buf.len()).next_multiple_of(sector_size)];
+        self.read_sectors(first_sector, &mut sectors)?;
+        buf.copy_from_slice(&sectors[head..head + buf.len()]);
+        Ok(())
+    }
+
+    /// Writes `buf` to the bytes starting at byte `offset`, reading and
+    /// rewriting the partially covered sectors at either end.
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> Result<(), Error> {
+        let sector_size = *self.sector_size();
+        let head = (offset % sector_size as u64) as usize;
+        let first_sector = offset / sector_size as u64;
+        if head == 0 && buf.len().is_multiple_of(sector_size) {
+            return self.write_sectors(first_sector, buf);
+        }
+        let mut sectors =
+            vec![0u8; (head + buf.len()).next_multiple_of(sector_size)];
+        let last_sector =
+            first_sector + (sectors.len() / sector_size) as u64 - 1;
+        let (first, rest) = sectors.split_at_mut(sector_size);
+        self.read_sectors(first_sector, first)?;
+        if last_sector != first_sector {
+            let last_start = rest.len() - sector_size;
+            self.read_sectors(last_sector, &mut rest[last_start..])?;
+        }
+        sectors[head..head + buf.len()].copy_from_slice(buf);
+        self.write_sectors(first_sector, &sectors)
+    }
+}
+
+impl<Device: BlockDevice + ?Sized> BlockDevice for &mut Device {
+    fn sector_size(&self) -> BytesPerSector {
+        (**self).sector_size()
+    }
+
+    fn sector_count(&self) -> u64 {
+        (**self).sector_count()
+    }
+
+    fn read_sectors(
+        &mut self,
+        first_sector: u64,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        (**self).read_sectors(first_sector, buf)
+    }
+
+    fn write_sectors(
+        &mut self,
+        first_sector: u64,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        (**self).write_sectors(first_sector, buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        (**self).flush()
+    }
+}
+
+#[cfg(feature = "std")]
+/// Adapts a [`Read`] + [`Write`] + [`Seek`] stream, such as a [`std::fs::File`]
+/// or a [`std::io::Cursor`], into a [`BlockDevice`].
+pub struct IoDevice<T> {
+    inner: T,
+    sector_size: BytesPerSector,
+    sector_count: u64,
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Write + Seek> IoDevice<T> {
+    /// Wraps `inner` as a device of 512 byte sectors spanning the whole
+    /// stream.
+    pub fn new(inner: T) -> Result<Self, Error> {
+        Self::with_sector_size(inner, BytesPerSector::new(512))
+    }
+
+    /// Wraps `inner` as a device of `sector_size` byte sectors spanning the
+    /// whole stream. A trailing partial sector is ignored.
+    pub fn with_sector_size(
+        mut inner: T,
+        sector_size: BytesPerSector,
+    ) -> Result<Self, Error> {
+        if !sector_size.is_power_of_two() {
+            return Err(Error::NotPowerOfTwo {
+                field: "sector_size",
+                value: *sector_size,
+            });
+        }
+        let len = inner.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            inner,
+            sector_size,
+            sector_count: len / *sector_size as u64,
+        })
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    fn check_range(&self, first_sector: u64, len: usize) -> Result<(), Error> {
+        if !len.is_multiple_of(*self.sector_size) {
+            return Err(Error::UnalignedBuffer {
+                len,
+                sector_size: *self.sector_size,
+            });
+        }
+        let sectors = (len / *self.sector_size) as u64;
+        match first_sector.checked_add(sectors) {
+            Some(end) if end <= self.sector_count => Ok(()),
+            _ => Err(Error::OutOfRange {
+                sector: first_sector.saturating_add(sectors.saturating_sub(1)),
+                sector_count: self.sector_count,
+            }),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Read + Write + Seek> BlockDevice for IoDevice<T> {
+    fn sector_size(&self) -> BytesPerSector {
+        self.sector_size
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn read_sectors(
+        &mut self,
+        first_sector: u64,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        self.check_range(first_sector, buf.len())?;
+        self.inner
+            .seek(SeekFrom::Start(first_sector * *self.sector_size as u64))?;
+        self.inner.read_exact(buf)?;
+        Ok(())
+    }
+
+    fn write_sectors(
+        &mut self,
+        first_sector: u64,
+        buf: &[u8],
+    ) -> Result<(), Error> {
+        self.check_range(first_sector, buf.len())?;
+        self.inner
+            .seek(SeekFrom::Start(first_sector * *self.sector_size as u64))?;
+        self.inner.write_all(buf)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::{error::Error, shift::BytesPerSector};
+
+    use super::{BlockDevice, IoDevice};
+
+    #[test]
+    pub fn unaligned_access() {
+        let mut disk = vec![0u8; 4096];
+        let mut device = IoDevice::with_sector_size(
+            Cursor::new(&mut disk),
+            BytesPerSector::new(1024),
+        )
+        .unwrap();
+        assert_eq!(device.sector_count(), 4);
+
+        let data: Vec<u8> = (0..1500).map(|i| i as u8).collect();
+        device.write_at(1000, &data).unwrap();
+        let mut read = vec![0u8; 1500];
+        device.read_at(1000, &mut read).unwrap();
+        assert_eq!(read, data);
+
+        assert!(matches!(
+            device.read_sectors(0, &mut [0u8; 100]),
+            Err(Error::UnalignedBuffer { .. })
+        ));
+        assert!(matches!(
+            device.read_at(4000, &mut [0u8; 100]),
+            Err(Error::OutOfRange { .. })
+        ));
+        // the last sector of the range does not fit in a u64
+        assert!(matches!(
+            device.read_sectors(u64::MAX, &mut [0u8; 2048]),
+            Err(Error::OutOfRange {
+                sector: u64::MAX,
+                ..
+            })
+        ));
+        let disk = device.into_inner().into_inner();
+        assert_eq!(&disk[1000..2500], &data[..]);
+        assert!(disk[..1000].iter().all(|b| *b == 0));
+        assert!(disk[2500..].iter().all(|b| *b == 0));
+    }
+}
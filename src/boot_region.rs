@@ -1,32 +1,77 @@
 mod checksum;
+mod sectors;
 
-use std::io::{Read, Seek, SeekFrom, Write};
+use alloc::vec;
 
-use checksum::Checksum;
+use bincode::config::Config;
+pub use checksum::ChecksumBuilder;
+pub use sectors::BootSectors;
 
 use crate::{
+    block_device::BlockDevice,
+    error::Error,
+    format_options::FormatOptions,
     oem::Oem,
-    shift::{BytesPerSector, SectorsPerCluster},
+    shift::{BytesPerSector, SectorsPerCluster, ShiftedBytes},
     super_block::{
-        boot_code::BootCode, extended_boot_code::ExtendedBootCode, SuperBlock,
+        boot_code::BootCode, extended_boot_code::ExtendedBootCode,
+        extended_boot_sector::index::Index, volume_flags::VolumeFlags,
+        BoundError, SuperBlock,
     },
+    time::TimeSource,
 };
 
-struct BootRegion<Disk> {
-    // #[sectors(0..1)]
-    boot_sector: SuperBlock,
-    // #[sectors(1..9)]
-    extended_boot_code: [ExtendedBootCode; 8],
-    // #[sectors(9..10)]
-    oem: Oem,
-    // #[sectors(10..11)]
-    _reserved: (),
-    // #[sectors(11..12)]
-    boot_checksum: Checksum,
+/// The number of sectors a boot region occupies.
+const SECTORS: usize = 12;
+/// The first sector of the Main Boot region.
+const MAIN_BOOT_SECTOR: u64 = 0;
+/// The first sector of the Backup Boot region.
+const BACKUP_BOOT_SECTOR: u64 = 12;
+
+/// Identifies one of the two copies of the boot region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// The Main Boot region, sectors 0 through 11.
+    Main,
+    /// The Backup Boot region, sectors 12 through 23.
+    Backup,
+}
+
+impl Region {
+    const fn first_sector(&self) -> u64 {
+        match self {
+            Region::Main => MAIN_BOOT_SECTOR,
+            Region::Backup => BACKUP_BOOT_SECTOR,
+        }
+    }
+}
+
+pub struct BootRegion<Disk> {
+    // #[sectors(0..12)]
+    main: BootSectors,
+    // #[sectors(12..24)]
+    // The Backup Boot region is kept identical to the Main Boot region on
+    // disk, save for the stale VolumeFlags and PercentInUse fields, and is
+    // only read on request.
+    /// Which region `main` was read from. This is only ever
+    /// [`Region::Backup`] when the Main Boot region was found to be corrupt
+    /// on open.
+    loaded_from: Region,
     disk: Disk,
 }
 
-impl<Disk: Read + Write + Seek> BootRegion<Disk> {
+fn bincode_options() -> impl Config {
+    bincode::config::legacy()
+}
+
+impl<Disk: BlockDevice> BootRegion<Disk> {
+    #[allow(clippy::too_many_arguments)]
+    /// Writes both the Main Boot and Backup Boot regions of a new volume.
+    ///
+    /// `options` pins down whatever the geometry does not, such as the volume
+    /// serial number.
+    ///
+    /// `volume_length` is in bytes and may not exceed the length of `disk`.
     pub fn format(
         bytes_per_sector: BytesPerSector,
         sectors_per_cluster: SectorsPerCluster,
@@ -34,133 +79,554 @@ impl<Disk: Read + Write + Seek> BootRegion<Disk> {
         extended_boot_code: [ExtendedBootCode; 8],
         volume_length: u64,
         oem: Oem,
+        options: &FormatOptions<impl TimeSource>,
         disk: Disk,
-    ) -> Self {
-        println!("Making superblock.");
+    ) -> Result<Self, Error> {
+        if volume_length > disk.len() {
+            return Err(BoundError::too_big(
+                "volume_length",
+                volume_length,
+                disk.len(),
+            )
+            .into());
+        }
         let boot_sector = SuperBlock::new(
             bytes_per_sector,
             sectors_per_cluster,
             boot_code,
             volume_length,
-        );
-        let bytes_per_sector = boot_sector.bytes_per_sector();
-        println!("Making boot region.");
+            options,
+        )?;
         let mut out = BootRegion {
-            boot_sector,
-            extended_boot_code,
-            oem,
-            _reserved: (),
+            main: BootSectors::new(boot_sector, extended_boot_code, oem),
+            loaded_from: Region::Main,
             disk,
-            boot_checksum: Checksum::new(0, bytes_per_sector),
         };
-        println!("Calculating checksum");
-        let checksum = out.calculate_checksum();
-        out.boot_checksum = Checksum::new(checksum, bytes_per_sector);
-        out.update_disk_checksum();
-        out
+        out.sync()?;
+        Ok(out)
     }
 
-    pub fn bytes_per_sector(&self) -> BytesPerSector {
-        self.boot_sector.bytes_per_sector()
-    }
-
-    pub fn calculate_checksum(&mut self) -> u32 {
-        let mut checksum = 0u32;
-        self.update_disk_without_checksum();
-        self.disk.seek(SeekFrom::Start(0)).unwrap();
-        let mut buf = vec![0u8; *self.bytes_per_sector() * 11];
-        self.disk.read_exact(buf.as_mut_slice()).unwrap();
-
-        for (i, byte) in buf.iter().enumerate() {
-            if [
-                106, 107, // volumeFlags
-                112, // percentInUse
-            ]
-            .contains(&i)
-            {
+    /// Reads and validates the boot region of an already formatted volume.
+    ///
+    /// The boot sector's signature and bounds are verified before any other
+    /// sector is read, after which every extended boot sector's signature and
+    /// the boot checksum are verified. Should the Main Boot region fail any of
+    /// these checks the Backup Boot region is used instead, which
+    /// [`BootRegion::loaded_from`] reports. Device errors are returned as is.
+    pub fn open(mut disk: Disk) -> Result<Self, Error> {
+        let main_error = match Self::read_main(&mut disk) {
+            Ok(main) => {
+                return Ok(Self {
+                    main,
+                    loaded_from: Region::Main,
+                    disk,
+                })
+            }
+            Err(e) if e.is_device_error() => return Err(e),
+            Err(e) => e,
+        };
+        match Self::probe_backup(&mut disk) {
+            Ok(backup) => Ok(Self {
+                main: backup,
+                loaded_from: Region::Backup,
+                disk,
+            }),
+            Err(e) if e.is_device_error() => Err(e),
+            // the main boot region's error is the more relevant one to
+            // report as it is the region implementations rely on.
+            Err(_) => Err(main_error),
+        }
+    }
+
+    fn read_main(disk: &mut Disk) -> Result<BootSectors, Error> {
+        // 512 bytes is the smallest legal sector size and the size of every
+        // field the boot sector defines.
+        let mut boot_sector = [0u8; 512];
+        disk.read_at(0, &mut boot_sector)?;
+        let boot_sector =
+            BootSectors::parse_boot_sector(&boot_sector, MAIN_BOOT_SECTOR)?;
+        Self::read_copy(disk, MAIN_BOOT_SECTOR, boot_sector.bytes_per_sector())
+    }
+
+    /// Finds the Backup Boot region without relying on the Main Boot region
+    /// by trying every legal sector size.
+    fn probe_backup(disk: &mut Disk) -> Result<BootSectors, Error> {
+        let mut last_error = Error::NotExfat;
+        for shift in 9..=12 {
+            let bytes_per_sector = ShiftedBytes::new(shift).unshift();
+            let mut boot_sector = [0u8; 512];
+            let start = BACKUP_BOOT_SECTOR * *bytes_per_sector as u64;
+            // the volume is too small to hold a backup boot region with
+            // sectors this big.
+            if start + boot_sector.len() as u64 > disk.len() {
+                break;
+            }
+            disk.read_at(start, &mut boot_sector)?;
+            let boot_sector = match BootSectors::parse_boot_sector(
+                &boot_sector,
+                BACKUP_BOOT_SECTOR,
+            ) {
+                Ok(boot_sector) => boot_sector,
+                Err(e) => {
+                    last_error = e;
+                    continue;
+                }
+            };
+            if *boot_sector.bytes_per_sector() != *bytes_per_sector {
                 continue;
             }
-            checksum = checksum.rotate_right(1) + (*byte as u32);
+            return Self::read_copy(disk, BACKUP_BOOT_SECTOR, bytes_per_sector);
         }
-        checksum
-    }
-    fn update_disk_without_checksum(&mut self) {
-        // vec implements write; appends to end
-        let mut d = &mut self.disk;
-        d.seek(SeekFrom::Start(0)).unwrap();
-        println!("Writing boot sector");
-        bincode::serialize_into(&mut d, &self.boot_sector).unwrap();
-        println!("Writing extended boot code");
-        bincode::serialize_into(&mut d, &self.extended_boot_code).unwrap();
-        println!("Writing oem");
-        bincode::serialize_into(&mut d, &self.oem).unwrap();
-        // one reserved empty sector
-        println!("Writing empty sector");
-        d.write_all(
-            (0..*self.boot_sector.bytes_per_sector())
-                .map(|_| 0u8)
-                .collect::<Vec<u8>>()
-                .as_ref(),
+        Err(last_error)
+    }
+
+    fn read_copy(
+        disk: &mut Disk,
+        first_sector: u64,
+        bytes_per_sector: BytesPerSector,
+    ) -> Result<BootSectors, Error> {
+        let mut sectors = vec![0u8; *bytes_per_sector * SECTORS];
+        disk.read_at(first_sector * *bytes_per_sector as u64, &mut sectors)?;
+        BootSectors::parse(&sectors, first_sector)
+    }
+
+    /// The boot region in use, which is the Main Boot region unless it was
+    /// corrupt on open.
+    pub fn main(&self) -> &BootSectors {
+        &self.main
+    }
+
+    /// Which region [`BootRegion::main`] was read from.
+    pub fn loaded_from(&self) -> Region {
+        self.loaded_from
+    }
+
+    /// Overwrites the Main Boot region with the contents of the Backup Boot
+    /// region after validating the latter.
+    pub fn restore_main_from_backup(&mut self) -> Result<(), Error> {
+        let bytes_per_sector = self.bytes_per_sector();
+        let region_len = *bytes_per_sector * SECTORS;
+        let mut sectors = vec![0u8; region_len];
+        self.disk.read_at(
+            BACKUP_BOOT_SECTOR * *bytes_per_sector as u64,
+            &mut sectors,
+        )?;
+        let backup = BootSectors::parse(&sectors, BACKUP_BOOT_SECTOR)?;
+        self.disk
+            .write_at(MAIN_BOOT_SECTOR * *bytes_per_sector as u64, &sectors)?;
+        self.disk.flush()?;
+        self.main = backup;
+        self.loaded_from = Region::Main;
+        Ok(())
+    }
+
+    /// Reads and validates the Backup Boot region from disk.
+    pub fn read_backup(&mut self) -> Result<BootSectors, Error> {
+        let bytes_per_sector = self.bytes_per_sector();
+        Self::read_copy(
+            &mut self.disk,
+            Region::Backup.first_sector(),
+            bytes_per_sector,
+        )
+    }
+
+    pub fn bytes_per_sector(&self) -> BytesPerSector {
+        self.main.bytes_per_sector()
+    }
+
+    pub fn boot_sector(&self) -> &SuperBlock {
+        self.main.boot_sector()
+    }
+
+    pub fn extended_boot_code(&self) -> &[ExtendedBootCode; 8] {
+        self.main.extended_boot_code()
+    }
+
+    pub fn oem(&self) -> &Oem {
+        self.main.oem()
+    }
+
+    pub fn boot_checksum(&self) -> u32 {
+        self.main.boot_checksum()
+    }
+
+    /// Replaces the boot code in both boot regions.
+    pub fn set_boot_code(&mut self, boot_code: BootCode) -> Result<(), Error> {
+        self.main.boot_sector_mut().set_boot_code(boot_code);
+        self.sync()
+    }
+
+    /// Replaces the code of one extended boot sector in both boot regions.
+    pub fn set_extended_boot_code(
+        &mut self,
+        index: Index,
+        code: &[u8],
+    ) -> Result<(), Error> {
+        let bytes_per_sector = self.bytes_per_sector();
+        self.main.extended_boot_code_mut()[*index as usize] =
+            ExtendedBootCode::new(code, bytes_per_sector)?;
+        self.sync()
+    }
+
+    /// Replaces the OEM parameters in both boot regions.
+    pub fn set_oem(&mut self, oem: Oem) -> Result<(), Error> {
+        self.main.set_oem(oem);
+        self.sync()
+    }
+
+    /// Points both boot regions at a new first cluster of the root directory.
+    pub fn set_first_cluster_of_root_directory(
+        &mut self,
+        cluster: u32,
+    ) -> Result<(), Error> {
+        self.main
+            .boot_sector_mut()
+            .set_first_cluster_of_root_directory(cluster);
+        self.sync()
+    }
+
+    /// Updates the VolumeFlags of the Main Boot region only, as the Backup
+    /// Boot region's copy of the field is considered stale.
+    pub fn set_volume_flags(
+        &mut self,
+        volume_flags: VolumeFlags,
+    ) -> Result<(), Error> {
+        self.main.boot_sector_mut().set_volume_flags(volume_flags);
+        self.write_boot_sector(MAIN_BOOT_SECTOR)
+    }
+
+    /// Updates the PercentInUse of the Main Boot region only, as the Backup
+    /// Boot region's copy of the field is considered stale.
+    pub fn set_percent_in_use(&mut self, percent: u8) -> Result<(), Error> {
+        self.main.boot_sector_mut().set_percent_in_use(percent);
+        self.write_boot_sector(MAIN_BOOT_SECTOR)
+    }
+
+    /// The disk the volume is on, for reading and writing the regions after
+    /// the boot regions.
+    pub fn disk_mut(&mut self) -> &mut Disk {
+        &mut self.disk
+    }
+
+    pub fn into_disk(self) -> Disk {
+        self.disk
+    }
+
+    /// Calculates the boot checksum of the in-memory boot region.
+    pub fn calculate_checksum(&self) -> Result<u32, Error> {
+        self.main.calculate_checksum()
+    }
+
+    /// Writes the in-memory boot region to both the Main Boot and Backup Boot
+    /// regions, recalculating the boot checksum.
+    fn sync(&mut self) -> Result<(), Error> {
+        let checksum = self.main.calculate_checksum()?;
+        self.main.set_boot_checksum(checksum);
+        for region in [Region::Main, Region::Backup] {
+            for index in 0..SECTORS {
+                self.write_sector(region.first_sector(), index)?;
+            }
+        }
+        self.disk.flush()
+    }
+
+    /// Writes the sector at `index` of the in-memory boot region to the boot
+    /// region starting at `first_sector`.
+    fn write_sector(
+        &mut self,
+        first_sector: u64,
+        index: usize,
+    ) -> Result<(), Error> {
+        let bytes_per_sector = *self.bytes_per_sector();
+        let mut buf = vec![0u8; bytes_per_sector];
+        self.main.serialize_sector(index, &mut buf)?;
+        self.disk.write_at(
+            (first_sector + index as u64) * bytes_per_sector as u64,
+            &buf,
         )
-        .unwrap();
     }
 
-    pub fn update_disk_checksum(&mut self) {
-        let start = *self.bytes_per_sector() as u64 * 11;
-        let d = &mut self.disk;
-        d.seek(SeekFrom::Start(start)).unwrap();
-        bincode::serialize_into(d, &self.boot_checksum).unwrap();
+    fn write_boot_sector(&mut self, first_sector: u64) -> Result<(), Error> {
+        self.write_sector(first_sector, 0)?;
+        self.disk.flush()
+    }
+
+    /// Writes the boot checksum sector of both boot regions.
+    pub fn update_disk_checksum(&mut self) -> Result<(), Error> {
+        for region in [Region::Main, Region::Backup] {
+            self.write_sector(region.first_sector(), 11)?;
+        }
+        self.disk.flush()
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use std::io::Cursor;
 
     use crate::{
-        oem::Oem,
+        block_device::IoDevice,
+        format_options::FormatOptions,
+        oem::{
+            flash::{FlashOptions, FlashParameter},
+            Oem,
+        },
         shift::{BytesPerSector, ShiftedBytes, ShiftedSectors},
         super_block::{
             boot_code::BootCode, extended_boot_code::ExtendedBootCode,
         },
+        time::{DateTime, FixedTime, TimeSource},
     };
 
-    use super::BootRegion;
+    use super::{BootRegion, BootSectors, ChecksumBuilder, Error, Region};
 
-    #[test]
-    pub fn test_format() {
-        const DISK_SIZE: usize = 2usize.pow(25);
-        let bytes_per_sector: BytesPerSector = ShiftedBytes::new(12).unshift();
-        let mut emulated_disk = vec![0u8; *bytes_per_sector * 12];
-        let disk = Cursor::new(&mut emulated_disk);
+    const DISK_SIZE: usize = 2usize.pow(25);
+
+    fn device(disk: &mut Vec<u8>) -> IoDevice<Cursor<&mut Vec<u8>>> {
+        IoDevice::new(Cursor::new(disk)).unwrap()
+    }
+
+    fn format(bytes_per_sector: BytesPerSector) -> Vec<u8> {
+        format_with(bytes_per_sector, &FormatOptions::default())
+    }
+
+    fn format_with(
+        bytes_per_sector: BytesPerSector,
+        options: &FormatOptions<impl TimeSource>,
+    ) -> Vec<u8> {
+        let mut emulated_disk = vec![0u8; DISK_SIZE];
+        let disk = IoDevice::new(Cursor::new(&mut emulated_disk)).unwrap();
         BootRegion::format(
             bytes_per_sector,
             ShiftedSectors::from(8).into(),
-            BootCode::new(&[]),
+            BootCode::new(&[]).unwrap(),
             core::array::from_fn(|_i| {
-                ExtendedBootCode::new(&[], bytes_per_sector)
+                ExtendedBootCode::new(&[], bytes_per_sector).unwrap()
             }),
             DISK_SIZE as u64,
             Oem::new(),
+            options,
             disk,
+        )
+        .unwrap();
+        emulated_disk
+    }
+
+    #[test]
+    pub fn test_format() {
+        let bytes_per_sector: BytesPerSector = ShiftedBytes::new(12).unshift();
+        let emulated_disk = format(bytes_per_sector);
+        assert_eq!(&emulated_disk[3..11], b"EXFAT   ");
+        assert_eq!(&emulated_disk[510..512], &[0x55, 0xAA]);
+        for sector in 1..9 {
+            let end = (sector + 1) * *bytes_per_sector;
+            assert_eq!(&emulated_disk[end - 4..end], &[0, 0, 0x55, 0xAA]);
+        }
+    }
+
+    #[test]
+    pub fn reproducible_format() {
+        let bytes_per_sector = ShiftedBytes::new(9).unshift();
+        let time = DateTime {
+            year: 2024,
+            month: 6,
+            day: 1,
+            ..Default::default()
+        };
+        let options = |serial| {
+            FormatOptions::reproducible(
+                time,
+                serial,
+                uguid::guid!("01234567-89ab-cdef-0123-456789abcdef"),
+            )
+        };
+        let first = format_with(bytes_per_sector, &options(0xDEAD_BEEF));
+        let second = format_with(bytes_per_sector, &options(0xDEAD_BEEF));
+        assert!(first == second);
+        assert_eq!(&first[100..104], &0xDEAD_BEEFu32.to_le_bytes());
+
+        let other = format_with(bytes_per_sector, &options(0xFEED_FACE));
+        assert!(first != other);
+
+        // without a fixed serial number it follows the time of formatting
+        let at = |time| {
+            format_with(bytes_per_sector, &FormatOptions::new(FixedTime(time)))
+        };
+        assert!(at(time) == at(time));
+        let later = DateTime { minute: 1, ..time };
+        assert!(at(time) != at(later));
+    }
+
+    #[test]
+    pub fn open_formatted() {
+        for shift in 9..=12 {
+            let bytes_per_sector = ShiftedBytes::new(shift).unshift();
+            let mut emulated_disk = format(bytes_per_sector);
+            let region = BootRegion::open(device(&mut emulated_disk))
+                .unwrap_or_else(|e| panic!("{}", e));
+            assert_eq!(*region.bytes_per_sector(), *bytes_per_sector);
+            assert!(region
+                .extended_boot_code()
+                .iter()
+                .all(|code| code.has_valid_signature()));
+        }
+    }
+
+    #[test]
+    pub fn open_rejects_corruption() {
+        let bytes_per_sector = ShiftedBytes::new(9).unshift();
+        let region_len = *bytes_per_sector * 12;
+        let formatted = format(bytes_per_sector);
+        // corrupts the same bytes of both boot regions
+        let corrupt_both = |offset: usize, bytes: &[u8]| {
+            let mut disk = formatted.clone();
+            disk[offset..offset + bytes.len()].copy_from_slice(bytes);
+            disk[region_len + offset..region_len + offset + bytes.len()]
+                .copy_from_slice(bytes);
+            disk
+        };
+
+        let mut disk = corrupt_both(510, &[0]);
+        assert!(matches!(
+            BootRegion::open(device(&mut disk)),
+            Err(Error::InvalidSignature { sector: 0 })
+        ));
+
+        let mut disk = corrupt_both(3 * *bytes_per_sector - 1, &[0]);
+        assert!(matches!(
+            BootRegion::open(device(&mut disk)),
+            Err(Error::InvalidSignature { sector: 2 })
+        ));
+
+        let mut disk = corrupt_both(3, b"F");
+        assert!(matches!(
+            BootRegion::open(device(&mut disk)),
+            Err(Error::NotExfat)
+        ));
+
+        // VolumeLength too small to hold a 1MB volume
+        let mut disk = corrupt_both(72, &1u64.to_le_bytes());
+        assert!(matches!(
+            BootRegion::open(device(&mut disk)),
+            Err(Error::Bound(_))
+        ));
+
+        let offset = 9 * *bytes_per_sector + 100;
+        let mut disk = corrupt_both(offset, &[!formatted[offset]]);
+        assert!(matches!(
+            BootRegion::open(device(&mut disk)),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+
+        // the VolumeFlags and PercentInUse fields are not checksummed
+        let mut disk = formatted.clone();
+        disk[106] = 0xFF;
+        disk[112] = 50;
+        let region = BootRegion::open(device(&mut disk)).unwrap();
+        assert_eq!(region.loaded_from(), Region::Main);
+    }
+
+    #[test]
+    pub fn open_falls_back_to_backup() {
+        for shift in [9, 12] {
+            let bytes_per_sector = ShiftedBytes::new(shift).unshift();
+            let formatted = format(bytes_per_sector);
+
+            // a torn boot sector
+            let mut disk = formatted.clone();
+            disk[..*bytes_per_sector].fill(0);
+            let mut region = BootRegion::open(device(&mut disk)).unwrap();
+            assert_eq!(region.loaded_from(), Region::Backup);
+            assert_eq!(*region.bytes_per_sector(), *bytes_per_sector);
+            region.restore_main_from_backup().unwrap();
+            assert_eq!(region.loaded_from(), Region::Main);
+            drop(region);
+            assert_eq!(disk, formatted);
+
+            // a checksum mismatch
+            let mut disk = formatted.clone();
+            disk[9 * *bytes_per_sector] ^= 0xFF;
+            let region = BootRegion::open(device(&mut disk)).unwrap();
+            assert_eq!(region.loaded_from(), Region::Backup);
+        }
+    }
+
+    #[test]
+    pub fn backup_matches_main() {
+        let bytes_per_sector = ShiftedBytes::new(9).unshift();
+        let mut disk = format(bytes_per_sector);
+        let region_len = *bytes_per_sector * 12;
+        assert_eq!(disk[..region_len], disk[region_len..region_len * 2]);
+
+        let mut region = BootRegion::open(device(&mut disk)).unwrap();
+        let backup = region.read_backup().unwrap();
+        assert_eq!(backup.boot_checksum(), region.boot_checksum());
+    }
+
+    #[test]
+    pub fn changes_are_mirrored_to_backup() {
+        let bytes_per_sector = ShiftedBytes::new(9).unshift();
+        let mut disk = format(bytes_per_sector);
+        let region_len = *bytes_per_sector * 12;
+
+        let mut region = BootRegion::open(device(&mut disk)).unwrap();
+        let checksum = region.boot_checksum();
+        let mut oem = Oem::new();
+        oem.insert(FlashParameter::new(FlashOptions::default()), 0)
+            .unwrap();
+        region.set_oem(oem).unwrap();
+        assert_ne!(region.boot_checksum(), checksum);
+        let backup = region.read_backup().unwrap();
+        assert_eq!(backup.boot_checksum(), region.boot_checksum());
+
+        // stale fields are only kept up to date in the main boot region
+        region.set_percent_in_use(50).unwrap();
+        drop(region);
+        assert_eq!(disk[112], 50);
+        assert_eq!(disk[region_len + 112], 0);
+        assert_eq!(disk[..112], disk[region_len..region_len + 112]);
+        assert_eq!(
+            disk[113..region_len],
+            disk[region_len + 113..region_len * 2]
         );
-        let no_zeros = emulated_disk.chunk_by(|a, b| a == b).map(|n| {
-            if (n)[0] != 0 || n.len() < 32 {
-                format!(
-                    "{}",
-                    n.iter()
-                        .map(|n| format!("{}", n))
-                        .collect::<Vec<String>>()
-                        .join("")
-                )
-            } else {
-                format!("\n({} zeroes)\n", n.len())
-            }
-        });
-        for byte in no_zeros {
-            print!("{byte}")
+        assert!(BootRegion::open(device(&mut disk)).is_ok());
+    }
+
+    #[test]
+    pub fn checksum_without_disk() {
+        let bytes_per_sector = ShiftedBytes::new(9).unshift();
+        let mut disk = format(bytes_per_sector);
+        let region_len = *bytes_per_sector * 12;
+        let checksummed = &disk[..*bytes_per_sector * 11];
+
+        // feeding the builder in uneven chunks makes no difference
+        let mut builder = ChecksumBuilder::new();
+        for chunk in checksummed.chunks(100) {
+            builder.update(chunk);
+        }
+        let expected = ChecksumBuilder::new().update(checksummed).finish();
+        assert_eq!(builder.finish(), expected);
+
+        let backup = BootSectors::from_bytes(&disk[region_len..]).unwrap();
+        assert_eq!(backup.boot_checksum(), expected);
+        assert_eq!(backup.calculate_checksum().unwrap(), expected);
+
+        let region = BootRegion::open(device(&mut disk)).unwrap();
+        assert_eq!(region.calculate_checksum().unwrap(), expected);
+    }
+
+    #[test]
+    pub fn truncated_region() {
+        let bytes_per_sector = ShiftedBytes::new(9).unshift();
+        let disk = format(bytes_per_sector);
+        let region_len = *bytes_per_sector * 12;
+        for len in [600, region_len - 1] {
+            assert!(matches!(
+                BootSectors::from_bytes(&disk[..len]),
+                Err(Error::BootRegionTooShort { required, .. })
+                    if required == region_len
+            ));
         }
-        println!("");
-        panic!();
+        assert!(BootSectors::from_bytes(&disk[..region_len]).is_ok());
     }
 }
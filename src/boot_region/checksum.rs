@@ -3,6 +3,8 @@ use crate::shift::BytesPerSector;
 mod de;
 mod ser;
 
+pub use de::BytesPerSectorSeed;
+
 pub struct Checksum {
     checksum: u32,
     sector_size: usize,
@@ -15,4 +17,47 @@ impl Checksum {
             sector_size: *sector_size,
         }
     }
+
+    pub fn checksum(&self) -> u32 {
+        self.checksum
+    }
+}
+
+/// Calculates the boot checksum of a boot region from its first 11 sectors,
+/// fed in order as they are produced.
+///
+/// The VolumeFlags and PercentInUse fields of the boot sector are skipped as
+/// the specification requires.
+#[derive(Default)]
+pub struct ChecksumBuilder {
+    checksum: u32,
+    /// How many bytes of the boot region have been consumed so far.
+    offset: usize,
+}
+
+impl ChecksumBuilder {
+    /// Offsets into the boot sector which are not part of the checksum.
+    const SKIPPED: [usize; 3] = [
+        106, 107, // volumeFlags
+        112, // percentInUse
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) -> &mut Self {
+        for byte in bytes {
+            if !Self::SKIPPED.contains(&self.offset) {
+                self.checksum =
+                    self.checksum.rotate_right(1).wrapping_add(*byte as u32);
+            }
+            self.offset += 1;
+        }
+        self
+    }
+
+    pub fn finish(&self) -> u32 {
+        self.checksum
+    }
 }
@@ -1,4 +1,4 @@
-use std::ops::Deref;
+use core::ops::Deref;
 
 use serde::de::DeserializeSeed;
 
@@ -12,7 +12,7 @@ impl Deref for BytesPerSectorSeed {
     type Target = usize;
 
     fn deref(&self) -> &Self::Target {
-        &(*self.0)
+        &self.0
     }
 }
 
@@ -29,8 +29,8 @@ impl<'de> DeserializeSeed<'de> for BytesPerSectorSeed {
             type Value = Checksum;
             fn expecting(
                 &self,
-                formatter: &mut std::fmt::Formatter,
-            ) -> std::fmt::Result {
+                formatter: &mut core::fmt::Formatter,
+            ) -> core::fmt::Result {
                 write!(
                     formatter,
                     "A repeating series of identical u32 checksums that \
@@ -48,7 +48,7 @@ impl<'de> DeserializeSeed<'de> for BytesPerSectorSeed {
                         &"a repeating series of checksums",
                     ),
                 )?;
-                for _ in 0..self.0 {
+                for _ in 1..self.0 / size_of::<u32>() {
                     let continued_checksum: u32 = seq.next_element()?.ok_or(
                         serde::de::Error::invalid_length(
                             self.0,
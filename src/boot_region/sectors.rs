@@ -0,0 +1,231 @@
+use alloc::{vec, vec::Vec};
+
+use serde::Serialize;
+
+use crate::{
+    error::Error,
+    oem::Oem,
+    shift::BytesPerSector,
+    super_block::{
+        extended_boot_code::{BytesPerSectorSeed, ExtendedBootCode},
+        SuperBlock,
+    },
+};
+
+use super::{
+    bincode_options,
+    checksum::{self, Checksum, ChecksumBuilder},
+    SECTORS,
+};
+
+/// One copy of the boot region: either the Main Boot region, which starts at
+/// sector 0, or the Backup Boot region, which starts at sector 12.
+pub struct BootSectors {
+    // #[sectors(0..1)]
+    boot_sector: SuperBlock,
+    // #[sectors(1..9)]
+    extended_boot_code: [ExtendedBootCode; 8],
+    // #[sectors(9..10)]
+    oem: Oem,
+    // #[sectors(10..11)]
+    _reserved: (),
+    // #[sectors(11..12)]
+    boot_checksum: Checksum,
+}
+
+impl BootSectors {
+    pub(super) fn new(
+        boot_sector: SuperBlock,
+        extended_boot_code: [ExtendedBootCode; 8],
+        oem: Oem,
+    ) -> Self {
+        let bytes_per_sector = boot_sector.bytes_per_sector();
+        Self {
+            boot_sector,
+            extended_boot_code,
+            oem,
+            _reserved: (),
+            boot_checksum: Checksum::new(0, bytes_per_sector),
+        }
+    }
+
+    /// Reads the boot sector out of the start of `sector`, verifying its
+    /// signature and bounds before anything else depends on it.
+    ///
+    /// `first_sector` is the volume-relative index of `sector`, used for
+    /// error reporting.
+    pub(super) fn parse_boot_sector(
+        sector: &[u8],
+        first_sector: u64,
+    ) -> Result<SuperBlock, Error> {
+        let (boot_sector, _): (SuperBlock, _) =
+            bincode::serde::decode_from_slice(sector, bincode_options())?;
+        if !boot_sector.has_valid_signature() {
+            return Err(Error::InvalidSignature {
+                sector: first_sector,
+            });
+        }
+        if !boot_sector.is_exfat() {
+            return Err(Error::NotExfat);
+        }
+        boot_sector.verify_bounds()?;
+        Ok(boot_sector)
+    }
+
+    /// Parses and validates all 12 sectors of a boot region.
+    ///
+    /// `first_sector` is the volume-relative index of the first sector in
+    /// `sectors`, used for error reporting.
+    pub(super) fn parse(
+        sectors: &[u8],
+        first_sector: u64,
+    ) -> Result<Self, Error> {
+        let boot_sector = Self::parse_boot_sector(sectors, first_sector)?;
+        let bytes_per_sector = boot_sector.bytes_per_sector();
+        let required = *bytes_per_sector * SECTORS;
+        if sectors.len() < required {
+            return Err(Error::BootRegionTooShort {
+                len: sectors.len(),
+                required,
+            });
+        }
+        let calculated = ChecksumBuilder::new()
+            .update(&sectors[..*bytes_per_sector * 11])
+            .finish();
+        let mut sectors = sectors.chunks_exact(*bytes_per_sector);
+        // the boot sector has already been read
+        sectors.next();
+
+        let mut extended_boot_code = Vec::with_capacity(8);
+        for (i, sector) in sectors.by_ref().take(8).enumerate() {
+            let (code, _) = bincode::serde::seed_decode_from_slice(
+                BytesPerSectorSeed(bytes_per_sector),
+                sector,
+                bincode_options(),
+            )?;
+            if !code.has_valid_signature() {
+                return Err(Error::InvalidSignature {
+                    sector: first_sector + i as u64 + 1,
+                });
+            }
+            extended_boot_code.push(code);
+        }
+        let Ok(extended_boot_code) = extended_boot_code.try_into() else {
+            unreachable!("exactly 8 extended boot sectors are read");
+        };
+
+        let (oem, _): (Oem, _) = bincode::serde::decode_from_slice(
+            sectors.next().expect("sector 9 is read"),
+            bincode_options(),
+        )?;
+        // the reserved sector carries no information
+        sectors.next();
+        let (boot_checksum, _): (Checksum, _) =
+            bincode::serde::seed_decode_from_slice(
+                checksum::BytesPerSectorSeed(bytes_per_sector),
+                sectors.next().expect("sector 11 is read"),
+                bincode_options(),
+            )?;
+        if calculated != boot_checksum.checksum() {
+            return Err(Error::ChecksumMismatch {
+                expected: boot_checksum.checksum(),
+                calculated,
+            });
+        }
+
+        Ok(Self {
+            boot_sector,
+            extended_boot_code,
+            oem,
+            _reserved: (),
+            boot_checksum,
+        })
+    }
+
+    /// Parses and validates a boot region held in memory, such as one copied
+    /// off of a read-only device.
+    ///
+    /// `sectors` must hold all 12 sectors of the region.
+    pub fn from_bytes(sectors: &[u8]) -> Result<Self, Error> {
+        Self::parse(sectors, 0)
+    }
+
+    /// Serializes the sector at `index` into `buf`, which must be exactly
+    /// one sector long. Whatever part of the sector no field covers is zero
+    /// filled.
+    pub(super) fn serialize_sector(
+        &self,
+        index: usize,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        fn serialize<T: Serialize>(
+            value: &T,
+            buf: &mut [u8],
+        ) -> Result<(), Error> {
+            buf.fill(0);
+            bincode::serde::encode_into_slice(value, buf, bincode_options())?;
+            Ok(())
+        }
+        match index {
+            0 => serialize(&self.boot_sector, buf),
+            1..=8 => serialize(&self.extended_boot_code[index - 1], buf),
+            9 => serialize(&self.oem, buf),
+            10 => serialize(&self._reserved, buf),
+            11 => serialize(&self.boot_checksum, buf),
+            _ => panic!(
+                "a boot region only has {} sectors, sector {} is out of range",
+                SECTORS, index
+            ),
+        }
+    }
+
+    /// Calculates the boot checksum of the region as it is in memory,
+    /// without touching any disk.
+    pub fn calculate_checksum(&self) -> Result<u32, Error> {
+        let mut builder = ChecksumBuilder::new();
+        let mut sector = vec![0u8; *self.bytes_per_sector()];
+        for index in 0..11 {
+            self.serialize_sector(index, &mut sector)?;
+            builder.update(&sector);
+        }
+        Ok(builder.finish())
+    }
+
+    pub fn bytes_per_sector(&self) -> BytesPerSector {
+        self.boot_sector.bytes_per_sector()
+    }
+
+    pub fn boot_sector(&self) -> &SuperBlock {
+        &self.boot_sector
+    }
+
+    pub(super) fn boot_sector_mut(&mut self) -> &mut SuperBlock {
+        &mut self.boot_sector
+    }
+
+    pub fn extended_boot_code(&self) -> &[ExtendedBootCode; 8] {
+        &self.extended_boot_code
+    }
+
+    pub(super) fn extended_boot_code_mut(
+        &mut self,
+    ) -> &mut [ExtendedBootCode; 8] {
+        &mut self.extended_boot_code
+    }
+
+    pub fn oem(&self) -> &Oem {
+        &self.oem
+    }
+
+    pub(super) fn set_oem(&mut self, oem: Oem) {
+        self.oem = oem;
+    }
+
+    pub fn boot_checksum(&self) -> u32 {
+        self.boot_checksum.checksum()
+    }
+
+    pub(super) fn set_boot_checksum(&mut self, checksum: u32) {
+        self.boot_checksum = Checksum::new(checksum, self.bytes_per_sector());
+    }
+}
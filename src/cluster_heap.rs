@@ -0,0 +1,60 @@
+//! # Purpose
+//! Locates clusters of the Cluster Heap, the region after the FATs which holds
+//! the Allocation Bitmap, the Up-case Table and every directory and file.
+
+use crate::{fat_entry::FatEntry, super_block::SuperBlock};
+
+/// Where the Cluster Heap of a volume is and how it is divided into clusters.
+#[derive(Debug, Clone, Copy)]
+pub struct ClusterHeap {
+    /// The byte offset of cluster 2, the first cluster of the heap.
+    offset: u64,
+    /// The log2 of the number of bytes per cluster.
+    cluster_shift: u8,
+    cluster_count: u32,
+}
+
+impl ClusterHeap {
+    /// Locates the Cluster Heap a boot sector describes.
+    pub fn new(boot_sector: &SuperBlock) -> Self {
+        let bytes_per_sector_shift = boot_sector.bytes_per_sector().shift();
+        let sectors_per_cluster_shift =
+            boot_sector.sectors_per_cluster().shift();
+        Self {
+            offset: (boot_sector.cluster_heap_offset() as u64)
+                << bytes_per_sector_shift.inner(),
+            cluster_shift: bytes_per_sector_shift.inner()
+                + sectors_per_cluster_shift.inner(),
+            cluster_count: boot_sector.cluster_count(),
+        }
+    }
+
+    pub fn cluster_count(&self) -> u32 {
+        self.cluster_count
+    }
+
+    /// The number of bytes in one cluster.
+    pub fn bytes_per_cluster(&self) -> u64 {
+        1 << self.cluster_shift
+    }
+
+    /// The number of clusters needed to hold `length` bytes.
+    pub fn clusters_for(&self, length: u64) -> u64 {
+        length.div_ceil(self.bytes_per_cluster())
+    }
+
+    /// The byte offset of `cluster` on the volume. `cluster` has to be part
+    /// of the heap, as checked by [`ClusterHeap::contains`].
+    pub fn cluster_offset(&self, cluster: u32) -> u64 {
+        debug_assert!(self.contains(cluster));
+        self.offset
+            + (((cluster - FatEntry::FIRST_CLUSTER) as u64)
+                << self.cluster_shift)
+    }
+
+    /// Whether `cluster` is one of clusters 2 through ClusterCount + 1.
+    pub fn contains(&self, cluster: u32) -> bool {
+        (FatEntry::FIRST_CLUSTER..=self.cluster_count.saturating_add(1))
+            .contains(&cluster)
+    }
+}
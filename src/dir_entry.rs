@@ -0,0 +1,417 @@
+//! # Purpose
+//! Defines the 32 byte directory entries directories consist of, and parses
+//! and serializes them.
+//!
+//! Every entry starts with an [`EntryType`], which decides how the remaining
+//! 31 bytes are laid out. Entries of types this crate does not know are kept
+//! as [`RawEntry`]s so that they survive being read and written back.
+
+pub mod allocation_bitmap;
+pub mod entry_type;
+pub mod file;
+pub mod file_attributes;
+pub mod file_name;
+pub mod general_flags;
+pub mod stream_extension;
+pub mod texfat_padding;
+pub mod upcase_table;
+pub mod vendor_allocation;
+pub mod vendor_extension;
+pub mod volume_guid;
+pub mod volume_label;
+
+use core::fmt::Display;
+
+use serde::{de::Visitor, ser::SerializeTuple, Deserialize, Serialize};
+
+pub use self::{
+    allocation_bitmap::AllocationBitmapEntry,
+    entry_type::{EntryType, TypeCategory, TypeImportance},
+    file::FileEntry,
+    file_attributes::FileAttributes,
+    file_name::FileNameEntry,
+    general_flags::{GeneralPrimaryFlags, GeneralSecondaryFlags},
+    stream_extension::StreamExtensionEntry,
+    texfat_padding::TexFatPaddingEntry,
+    upcase_table::UpcaseTableEntry,
+    vendor_allocation::VendorAllocationEntry,
+    vendor_extension::VendorExtensionEntry,
+    volume_guid::VolumeGuidEntry,
+    volume_label::VolumeLabelEntry,
+};
+
+use crate::error::Error as CrateError;
+
+/// Ways a directory entry can be malformed.
+#[derive(Debug)]
+pub enum Error {
+    /// EntryType 80h, a critical primary entry of TypeCode 0, is invalid.
+    InvalidEntryType(u8),
+    /// A volume label is longer than the 11 code units its entry holds.
+    LabelTooLong(usize),
+    /// A volume label holds a code unit which file names cannot.
+    InvalidLabel,
+    /// Fewer than [`DirEntry::SIZE`] bytes were given to parse an entry from.
+    TooShort(usize),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::InvalidEntryType(entry_type) => write!(
+                f,
+                "Directory entry type {:#04x} is invalid.",
+                entry_type
+            ),
+            Error::LabelTooLong(length) => write!(
+                f,
+                "A volume label of {} code units is longer than the maximum \
+                of {}.",
+                length,
+                VolumeLabelEntry::MAX_LENGTH
+            ),
+            Error::InvalidLabel => write!(
+                f,
+                "A volume label cannot contain control characters or any of \
+                \"*/:<>?\\|."
+            ),
+            Error::TooShort(length) => write!(
+                f,
+                "A directory entry needs {} bytes but only {} were given.",
+                DirEntry::SIZE,
+                length
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// A directory entry of a type this crate does not interpret, kept byte for
+/// byte.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawEntry(pub [u8; DirEntry::SIZE]);
+
+impl RawEntry {
+    pub fn entry_type(&self) -> EntryType {
+        EntryType::from_byte(self.0[0])
+    }
+}
+
+/// A directory entry of any type. Entries which are not in use, such as
+/// those of deleted files, are parsed the same as those which are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirEntry {
+    /// The entry and every one after it in the directory is unused.
+    EndOfDirectory,
+    AllocationBitmap(AllocationBitmapEntry),
+    UpcaseTable(UpcaseTableEntry),
+    VolumeLabel(VolumeLabelEntry),
+    File(FileEntry),
+    VolumeGuid(VolumeGuidEntry),
+    TexFatPadding(TexFatPaddingEntry),
+    StreamExtension(StreamExtensionEntry),
+    FileName(FileNameEntry),
+    VendorExtension(VendorExtensionEntry),
+    VendorAllocation(VendorAllocationEntry),
+    /// An entry of a type this crate does not know. Implementations shall
+    /// preserve unrecognized benign entries.
+    Unknown(RawEntry),
+}
+
+/// Implements `entry_type` for entries which keep their EntryType in a field
+/// of the same name.
+macro_rules! entry_type {
+    ($($entry:ty),*) => {
+        $(impl $entry {
+            pub fn entry_type(&self) -> EntryType {
+                self.entry_type
+            }
+        })*
+    };
+}
+
+entry_type!(
+    AllocationBitmapEntry,
+    UpcaseTableEntry,
+    VolumeLabelEntry,
+    FileEntry,
+    VolumeGuidEntry,
+    TexFatPaddingEntry,
+    StreamExtensionEntry,
+    FileNameEntry,
+    VendorExtensionEntry,
+    VendorAllocationEntry
+);
+
+impl DirEntry {
+    /// The size of every directory entry in bytes.
+    pub const SIZE: usize = 32;
+
+    /// Parses the first [`DirEntry::SIZE`] bytes of `bytes`.
+    pub fn parse(bytes: &[u8]) -> Result<Self, CrateError> {
+        if bytes.len() < Self::SIZE {
+            return Err(Error::TooShort(bytes.len()).into());
+        }
+        let entry_type = EntryType::from_byte(bytes[0]);
+        if entry_type == EntryType::END_OF_DIRECTORY {
+            return Ok(Self::EndOfDirectory);
+        }
+        if entry_type == EntryType::INVALID {
+            return Err(Error::InvalidEntryType(entry_type.to_byte()).into());
+        }
+        let types = [
+            EntryType::ALLOCATION_BITMAP,
+            EntryType::UPCASE_TABLE,
+            EntryType::VOLUME_LABEL,
+            EntryType::FILE,
+            EntryType::VOLUME_GUID,
+            EntryType::TEXFAT_PADDING,
+            EntryType::STREAM_EXTENSION,
+            EntryType::FILE_NAME,
+            EntryType::VENDOR_EXTENSION,
+            EntryType::VENDOR_ALLOCATION,
+        ];
+        let known = types.into_iter().find(|known| known.same_type(entry_type));
+        Ok(match known {
+            Some(EntryType::ALLOCATION_BITMAP) => {
+                Self::AllocationBitmap(decode(bytes)?)
+            }
+            Some(EntryType::UPCASE_TABLE) => Self::UpcaseTable(decode(bytes)?),
+            Some(EntryType::VOLUME_LABEL) => Self::VolumeLabel(decode(bytes)?),
+            Some(EntryType::FILE) => Self::File(decode(bytes)?),
+            Some(EntryType::VOLUME_GUID) => Self::VolumeGuid(decode(bytes)?),
+            Some(EntryType::TEXFAT_PADDING) => {
+                Self::TexFatPadding(decode(bytes)?)
+            }
+            Some(EntryType::STREAM_EXTENSION) => {
+                Self::StreamExtension(decode(bytes)?)
+            }
+            Some(EntryType::FILE_NAME) => Self::FileName(decode(bytes)?),
+            Some(EntryType::VENDOR_EXTENSION) => {
+                Self::VendorExtension(decode(bytes)?)
+            }
+            Some(EntryType::VENDOR_ALLOCATION) => {
+                Self::VendorAllocation(decode(bytes)?)
+            }
+            _ => Self::Unknown(decode(bytes)?),
+        })
+    }
+
+    /// The entry as it is stored on disk.
+    pub fn to_bytes(&self) -> Result<[u8; Self::SIZE], CrateError> {
+        let mut bytes = [0u8; Self::SIZE];
+        bincode::serde::encode_into_slice(
+            self,
+            &mut bytes,
+            bincode::config::legacy(),
+        )?;
+        Ok(bytes)
+    }
+
+    pub fn entry_type(&self) -> EntryType {
+        match self {
+            DirEntry::EndOfDirectory => EntryType::END_OF_DIRECTORY,
+            DirEntry::AllocationBitmap(entry) => entry.entry_type(),
+            DirEntry::UpcaseTable(entry) => entry.entry_type(),
+            DirEntry::VolumeLabel(entry) => entry.entry_type(),
+            DirEntry::File(entry) => entry.entry_type(),
+            DirEntry::VolumeGuid(entry) => entry.entry_type(),
+            DirEntry::TexFatPadding(entry) => entry.entry_type(),
+            DirEntry::StreamExtension(entry) => entry.entry_type(),
+            DirEntry::FileName(entry) => entry.entry_type(),
+            DirEntry::VendorExtension(entry) => entry.entry_type(),
+            DirEntry::VendorAllocation(entry) => entry.entry_type(),
+            DirEntry::Unknown(entry) => entry.entry_type(),
+        }
+    }
+}
+
+fn decode<T: serde::de::DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<T, CrateError> {
+    let (entry, _) =
+        bincode::serde::decode_from_slice(bytes, bincode::config::legacy())?;
+    Ok(entry)
+}
+
+impl Serialize for DirEntry {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            DirEntry::EndOfDirectory => {
+                let mut seq = serializer.serialize_tuple(Self::SIZE)?;
+                for _ in 0..Self::SIZE {
+                    seq.serialize_element(&0u8)?;
+                }
+                seq.end()
+            }
+            DirEntry::AllocationBitmap(entry) => entry.serialize(serializer),
+            DirEntry::UpcaseTable(entry) => entry.serialize(serializer),
+            DirEntry::VolumeLabel(entry) => entry.serialize(serializer),
+            DirEntry::File(entry) => entry.serialize(serializer),
+            DirEntry::VolumeGuid(entry) => entry.serialize(serializer),
+            DirEntry::TexFatPadding(entry) => entry.serialize(serializer),
+            DirEntry::StreamExtension(entry) => entry.serialize(serializer),
+            DirEntry::FileName(entry) => entry.serialize(serializer),
+            DirEntry::VendorExtension(entry) => entry.serialize(serializer),
+            DirEntry::VendorAllocation(entry) => entry.serialize(serializer),
+            DirEntry::Unknown(entry) => entry.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for DirEntry {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct DirEntryVisitor;
+        impl<'de> Visitor<'de> for DirEntryVisitor {
+            type Value = DirEntry;
+
+            fn expecting(
+                &self,
+                formatter: &mut core::fmt::Formatter,
+            ) -> core::fmt::Result {
+                formatter.write_str("a 32 byte directory entry")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                // the entry type decides how the rest is laid out, so the
+                // whole entry is read before any of it is interpreted.
+                let mut bytes = [0u8; DirEntry::SIZE];
+                for (index, byte) in bytes.iter_mut().enumerate() {
+                    *byte = seq.next_element()?.ok_or(
+                        serde::de::Error::invalid_length(index, &self),
+                    )?;
+                }
+                DirEntry::parse(&bytes).map_err(serde::de::Error::custom)
+            }
+        }
+        deserializer.deserialize_tuple(Self::SIZE, DirEntryVisitor)
+    }
+}
+
+/// Calculates the SetChecksum of a directory entry set from its entries as
+/// they are stored on disk, skipping the SetChecksum field of the primary
+/// entry.
+pub fn set_checksum(entries: &[u8]) -> u16 {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !matches!(index, 2 | 3))
+        .fold(0u16, |checksum, (_, byte)| {
+            checksum.rotate_right(1).wrapping_add(*byte as u16)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use uguid::guid;
+
+    use super::{
+        set_checksum, AllocationBitmapEntry, DirEntry, EntryType,
+        FileAttributes, FileEntry, FileNameEntry, RawEntry,
+        StreamExtensionEntry, TypeCategory, TypeImportance, UpcaseTableEntry,
+        VendorExtensionEntry, VolumeGuidEntry, VolumeLabelEntry,
+    };
+
+    #[test]
+    pub fn entry_type() {
+        let file = EntryType::FILE;
+        assert_eq!(file.type_code(), 5);
+        assert_eq!(file.importance(), TypeImportance::Critical);
+        assert_eq!(file.category(), TypeCategory::Primary);
+        assert!(file.in_use());
+        let deleted = file.with_in_use(false);
+        assert_eq!(deleted.to_byte(), 0x05);
+        assert!(deleted.same_type(file));
+
+        let name = EntryType::FILE_NAME;
+        assert_eq!(name.type_code(), 1);
+        assert_eq!(name.category(), TypeCategory::Secondary);
+        let vendor = EntryType::VENDOR_EXTENSION;
+        assert_eq!(vendor.importance(), TypeImportance::Benign);
+        assert_eq!(vendor.category(), TypeCategory::Secondary);
+    }
+
+    #[test]
+    pub fn round_trip() {
+        let mut name = [0u16; 15];
+        name[..5].copy_from_slice(&b"hello".map(u16::from));
+        let entries = [
+            DirEntry::AllocationBitmap(AllocationBitmapEntry::new(1, 2, 100)),
+            DirEntry::UpcaseTable(UpcaseTableEntry::new(0xE619D30D, 3, 5836)),
+            DirEntry::VolumeLabel(VolumeLabelEntry::new(&[b'X' as u16; 3])),
+            DirEntry::File(FileEntry::new(2, FileAttributes::DIRECTORY)),
+            DirEntry::VolumeGuid(
+                VolumeGuidEntry::new(guid!(
+                    "01234567-89ab-cdef-0123-456789abcdef"
+                ))
+                .unwrap(),
+            ),
+            DirEntry::StreamExtension(StreamExtensionEntry::new(5, 0xBEEF)),
+            DirEntry::FileName(FileNameEntry::new(name)),
+            DirEntry::VendorExtension(VendorExtensionEntry::new(
+                guid!("fedcba98-7654-3210-fedc-ba9876543210"),
+                [7; 14],
+            )),
+            DirEntry::EndOfDirectory,
+        ];
+        for entry in entries {
+            let bytes = entry.to_bytes().unwrap();
+            assert_eq!(bytes[0], entry.entry_type().to_byte());
+            assert_eq!(DirEntry::parse(&bytes).unwrap(), entry);
+        }
+
+        let stream = StreamExtensionEntry::new(5, 0xBEEF);
+        let bytes = DirEntry::StreamExtension(stream).to_bytes().unwrap();
+        assert_eq!(bytes[..8], [0xC0, 0b01, 0, 5, 0xEF, 0xBE, 0, 0]);
+        let label = VolumeLabelEntry::new(&[b'A' as u16; 12]);
+        assert_eq!(label.label(), [b'A' as u16; 11]);
+    }
+
+    #[test]
+    pub fn deleted_and_unknown_entries() {
+        let mut bytes =
+            DirEntry::File(FileEntry::new(2, FileAttributes::empty()))
+                .to_bytes()
+                .unwrap();
+        bytes[0] = EntryType::FILE.with_in_use(false).to_byte();
+        let deleted = DirEntry::parse(&bytes).unwrap();
+        assert!(matches!(deleted, DirEntry::File(_)));
+        assert!(!deleted.entry_type().in_use());
+        assert_eq!(deleted.to_bytes().unwrap(), bytes);
+
+        // a benign primary entry from a later revision is kept as is
+        let mut unknown = [0x5A; 32];
+        unknown[0] = 0xA7;
+        let entry = DirEntry::parse(&unknown).unwrap();
+        assert_eq!(entry, DirEntry::Unknown(RawEntry(unknown)));
+        assert_eq!(entry.to_bytes().unwrap(), unknown);
+
+        assert!(DirEntry::parse(&[0x80; 32]).is_err());
+        assert!(DirEntry::parse(&[]).is_err());
+        assert!(DirEntry::parse(&[0x85; 31]).is_err());
+    }
+
+    #[test]
+    pub fn volume_guid_checksum() {
+        let guid = guid!("01234567-89ab-cdef-0123-456789abcdef");
+        let entry = VolumeGuidEntry::new(guid).unwrap();
+        assert_eq!(entry.volume_guid(), guid);
+        let bytes = DirEntry::VolumeGuid(entry).to_bytes().unwrap();
+        assert_eq!(bytes[0], 0xA0);
+        assert_eq!(bytes[6..22], guid.to_bytes());
+        assert_eq!(
+            u16::from_le_bytes([bytes[2], bytes[3]]),
+            set_checksum(&bytes)
+        );
+    }
+}
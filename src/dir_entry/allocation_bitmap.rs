@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use super::EntryType;
+
+/// The Allocation Bitmap directory entry, which records where an Allocation
+/// Bitmap is. It only ever appears in the root directory, once per FAT.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocationBitmapEntry {
+    pub(super) entry_type: EntryType,
+    /// Only the first bit is defined, BitmapIdentifier. It is 0 for the First
+    /// Allocation Bitmap and 1 for the Second Allocation Bitmap, which only
+    /// TexFAT volumes have.
+    bitmap_flags: u8,
+    reserved: [u8; 18],
+    /// The index of the first cluster of the cluster chain, as the FAT
+    /// describes, which hosts the Allocation Bitmap.
+    pub first_cluster: u32,
+    /// The size of the Allocation Bitmap in bytes, which is ClusterCount / 8
+    /// rounded up.
+    pub data_length: u64,
+}
+
+impl AllocationBitmapEntry {
+    pub fn new(bitmap_identifier: u8, first_cluster: u32, length: u64) -> Self {
+        Self {
+            entry_type: EntryType::ALLOCATION_BITMAP,
+            bitmap_flags: bitmap_identifier & 1,
+            reserved: [0; 18],
+            first_cluster,
+            data_length: length,
+        }
+    }
+
+    /// 0 for the First Allocation Bitmap, 1 for the Second.
+    pub fn bitmap_identifier(&self) -> u8 {
+        self.bitmap_flags & 1
+    }
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+/// Whether a directory entry has to be understood to use its directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeImportance {
+    /// Implementations shall not use a directory holding an unrecognized
+    /// critical primary entry, and shall not use an entry set holding an
+    /// unrecognized critical secondary entry.
+    Critical,
+    /// Unrecognized benign entries are ignored, and preserved.
+    Benign,
+}
+
+/// Whether a directory entry starts an entry set or belongs to one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeCategory {
+    Primary,
+    Secondary,
+}
+
+/// The EntryType field every directory entry starts with.
+///
+/// Its bits are, from the lowest: TypeCode (5 bits), TypeImportance,
+/// TypeCategory and InUse.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntryType(u8);
+
+impl EntryType {
+    const TYPE_CODE: u8 = 0b0001_1111;
+    const TYPE_IMPORTANCE: u8 = 0b0010_0000;
+    const TYPE_CATEGORY: u8 = 0b0100_0000;
+    const IN_USE: u8 = 0b1000_0000;
+
+    /// Marks the end of a directory: this and every later entry is unused.
+    pub const END_OF_DIRECTORY: Self = Self(0x00);
+    /// TypeCode 0 with InUse set is invalid.
+    pub const INVALID: Self = Self(0x80);
+
+    pub const ALLOCATION_BITMAP: Self = Self(0x81);
+    pub const UPCASE_TABLE: Self = Self(0x82);
+    pub const VOLUME_LABEL: Self = Self(0x83);
+    pub const FILE: Self = Self(0x85);
+    pub const VOLUME_GUID: Self = Self(0xA0);
+    pub const TEXFAT_PADDING: Self = Self(0xA1);
+    pub const STREAM_EXTENSION: Self = Self(0xC0);
+    pub const FILE_NAME: Self = Self(0xC1);
+    pub const VENDOR_EXTENSION: Self = Self(0xE0);
+    pub const VENDOR_ALLOCATION: Self = Self(0xE1);
+
+    pub const fn from_byte(byte: u8) -> Self {
+        Self(byte)
+    }
+
+    pub const fn to_byte(self) -> u8 {
+        self.0
+    }
+
+    /// Distinguishes the entries of the same importance and category.
+    pub const fn type_code(self) -> u8 {
+        self.0 & Self::TYPE_CODE
+    }
+
+    pub const fn importance(self) -> TypeImportance {
+        if self.0 & Self::TYPE_IMPORTANCE == 0 {
+            TypeImportance::Critical
+        } else {
+            TypeImportance::Benign
+        }
+    }
+
+    pub const fn category(self) -> TypeCategory {
+        if self.0 & Self::TYPE_CATEGORY == 0 {
+            TypeCategory::Primary
+        } else {
+            TypeCategory::Secondary
+        }
+    }
+
+    /// Whether the entry is in use. Deleted entries keep their type with
+    /// this bit cleared.
+    pub const fn in_use(self) -> bool {
+        self.0 & Self::IN_USE != 0
+    }
+
+    pub const fn with_in_use(self, in_use: bool) -> Self {
+        if in_use {
+            Self(self.0 | Self::IN_USE)
+        } else {
+            Self(self.0 & !Self::IN_USE)
+        }
+    }
+
+    /// Whether two entry types are the same, regardless of InUse.
+    pub const fn same_type(self, other: Self) -> bool {
+        self.0 & !Self::IN_USE == other.0 & !Self::IN_USE
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::time::{self, Timestamp};
+
+use super::{EntryType, FileAttributes};
+
+/// The File directory entry, the primary entry of the entry set describing a
+/// file or directory. It is followed by a Stream Extension entry and File
+/// Name entries.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileEntry {
+    pub(super) entry_type: EntryType,
+    /// The number of secondary entries following this one, 2 through 18.
+    pub secondary_count: u8,
+    /// The checksum of the whole entry set, see [`super::set_checksum`].
+    pub set_checksum: u16,
+    pub file_attributes: FileAttributes,
+    reserved1: u16,
+    pub create_timestamp: u32,
+    pub last_modified_timestamp: u32,
+    pub last_accessed_timestamp: u32,
+    pub create_10ms_increment: u8,
+    pub last_modified_10ms_increment: u8,
+    pub create_utc_offset: u8,
+    pub last_modified_utc_offset: u8,
+    pub last_accessed_utc_offset: u8,
+    reserved2: [u8; 7],
+}
+
+impl FileEntry {
+    pub fn new(secondary_count: u8, file_attributes: FileAttributes) -> Self {
+        Self {
+            entry_type: EntryType::FILE,
+            secondary_count,
+            set_checksum: 0,
+            file_attributes,
+            reserved1: 0,
+            create_timestamp: 0,
+            last_modified_timestamp: 0,
+            last_accessed_timestamp: 0,
+            create_10ms_increment: 0,
+            last_modified_10ms_increment: 0,
+            create_utc_offset: 0,
+            last_modified_utc_offset: 0,
+            last_accessed_utc_offset: 0,
+            reserved2: [0; 7],
+        }
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.file_attributes.contains(FileAttributes::DIRECTORY)
+    }
+
+    pub fn created(&self) -> Result<Timestamp, time::Error> {
+        Timestamp::decode(
+            self.create_timestamp,
+            self.create_10ms_increment,
+            self.create_utc_offset,
+        )
+    }
+
+    pub fn set_created(&mut self, timestamp: Timestamp) {
+        (
+            self.create_timestamp,
+            self.create_10ms_increment,
+            self.create_utc_offset,
+        ) = timestamp.encode();
+    }
+
+    pub fn modified(&self) -> Result<Timestamp, time::Error> {
+        Timestamp::decode(
+            self.last_modified_timestamp,
+            self.last_modified_10ms_increment,
+            self.last_modified_utc_offset,
+        )
+    }
+
+    pub fn set_modified(&mut self, timestamp: Timestamp) {
+        (
+            self.last_modified_timestamp,
+            self.last_modified_10ms_increment,
+            self.last_modified_utc_offset,
+        ) = timestamp.encode();
+    }
+
+    /// When the file was last accessed, which is only recorded to two
+    /// seconds.
+    pub fn accessed(&self) -> Result<Timestamp, time::Error> {
+        Timestamp::decode(
+            self.last_accessed_timestamp,
+            0,
+            self.last_accessed_utc_offset,
+        )
+    }
+
+    /// Stamps the entry as accessed at `timestamp`, rounded down to two
+    /// seconds as there is no 10msIncrement field for it.
+    pub fn set_accessed(&mut self, timestamp: Timestamp) {
+        let (accessed, _, utc_offset) = timestamp.encode();
+        self.last_accessed_timestamp = accessed;
+        self.last_accessed_utc_offset = utc_offset;
+    }
+}
@@ -0,0 +1,83 @@
+use core::ops::{BitAnd, BitOr, BitOrAssign};
+
+use serde::{Deserialize, Serialize};
+
+/// The FileAttributes field of a File directory entry.
+///
+/// Only five bits are defined, the rest are reserved. Every bit is kept as it
+/// was read, so that copying attributes from one entry to another preserves
+/// them exactly.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default,
+)]
+pub struct FileAttributes(u16);
+
+impl FileAttributes {
+    /// The file cannot be written to.
+    pub const READ_ONLY: Self = Self(1 << 0);
+    /// The file is not listed by default.
+    pub const HIDDEN: Self = Self(1 << 1);
+    /// The file is used by the operating system.
+    pub const SYSTEM: Self = Self(1 << 2);
+    /// The entry set describes a directory rather than a file.
+    pub const DIRECTORY: Self = Self(1 << 4);
+    /// The file has changed since it was last backed up.
+    pub const ARCHIVE: Self = Self(1 << 5);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The attributes with the bits of `bits`, reserved ones included.
+    pub const fn from_bits(bits: u16) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Whether every bit set in `other` is set in `self`.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Inserts `other` if `value`, otherwise removes it.
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+impl BitOr for FileAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for FileAttributes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.insert(rhs);
+    }
+}
+
+impl BitAnd for FileAttributes {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
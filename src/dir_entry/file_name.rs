@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use super::{EntryType, GeneralSecondaryFlags};
+
+/// The File Name directory entry, which holds up to 15 UTF-16 code units of
+/// the name of a file. Names longer than that span several entries.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileNameEntry {
+    pub(super) entry_type: EntryType,
+    general_secondary_flags: GeneralSecondaryFlags,
+    pub file_name: [u16; 15],
+}
+
+impl FileNameEntry {
+    /// The number of UTF-16 code units one entry holds.
+    pub const LENGTH: usize = 15;
+
+    pub fn new(file_name: [u16; 15]) -> Self {
+        Self {
+            entry_type: EntryType::FILE_NAME,
+            general_secondary_flags: GeneralSecondaryFlags::default(),
+            file_name,
+        }
+    }
+}
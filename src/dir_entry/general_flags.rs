@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+
+const ALLOCATION_POSSIBLE: u8 = 0b01;
+const NO_FAT_CHAIN: u8 = 0b10;
+
+/// The GeneralPrimaryFlags field of a primary directory entry. Only the low
+/// two bits are defined, the rest are custom defined.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default,
+)]
+pub struct GeneralPrimaryFlags(u16);
+
+/// The GeneralSecondaryFlags field of a secondary directory entry. Only the
+/// low two bits are defined, the rest are custom defined.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default,
+)]
+pub struct GeneralSecondaryFlags(u8);
+
+impl GeneralPrimaryFlags {
+    pub const fn new(allocation_possible: bool, no_fat_chain: bool) -> Self {
+        Self(
+            GeneralSecondaryFlags::new(allocation_possible, no_fat_chain).0
+                as u16,
+        )
+    }
+
+    /// Whether the entry describes an allocation in the Cluster Heap.
+    pub const fn allocation_possible(self) -> bool {
+        self.0 & ALLOCATION_POSSIBLE as u16 != 0
+    }
+
+    /// Whether the allocation is one contiguous series of clusters, in which
+    /// case the FAT entries of its clusters are not used.
+    pub const fn no_fat_chain(self) -> bool {
+        self.0 & NO_FAT_CHAIN as u16 != 0
+    }
+}
+
+impl GeneralSecondaryFlags {
+    pub const fn new(allocation_possible: bool, no_fat_chain: bool) -> Self {
+        let mut flags = 0;
+        if allocation_possible {
+            flags |= ALLOCATION_POSSIBLE;
+        }
+        if no_fat_chain {
+            flags |= NO_FAT_CHAIN;
+        }
+        Self(flags)
+    }
+
+    /// Whether the entry describes an allocation in the Cluster Heap.
+    pub const fn allocation_possible(self) -> bool {
+        self.0 & ALLOCATION_POSSIBLE != 0
+    }
+
+    /// Whether the allocation is one contiguous series of clusters, in which
+    /// case the FAT entries of its clusters are not used.
+    pub const fn no_fat_chain(self) -> bool {
+        self.0 & NO_FAT_CHAIN != 0
+    }
+
+    pub fn set_no_fat_chain(&mut self, no_fat_chain: bool) {
+        if no_fat_chain {
+            self.0 |= NO_FAT_CHAIN;
+        } else {
+            self.0 &= !NO_FAT_CHAIN;
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+
+use super::{EntryType, GeneralSecondaryFlags};
+
+/// The Stream Extension directory entry, the first secondary entry of a File
+/// entry set, which records where the contents of the file are.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamExtensionEntry {
+    pub(super) entry_type: EntryType,
+    pub general_secondary_flags: GeneralSecondaryFlags,
+    reserved1: u8,
+    /// The length of the name in the File Name entries, in UTF-16 code
+    /// units.
+    pub name_length: u8,
+    /// The hash of the up-cased name, see [`crate::entry_set::name_hash`].
+    pub name_hash: u16,
+    reserved2: u16,
+    /// How far into the file data has been written. Reading past it yields
+    /// zeroes.
+    pub valid_data_length: u64,
+    reserved3: u32,
+    /// The first cluster of the contents, 0 when the file is empty.
+    pub first_cluster: u32,
+    /// The length of the file in bytes.
+    pub data_length: u64,
+}
+
+impl StreamExtensionEntry {
+    pub fn new(name_length: u8, name_hash: u16) -> Self {
+        Self {
+            entry_type: EntryType::STREAM_EXTENSION,
+            general_secondary_flags: GeneralSecondaryFlags::new(true, false),
+            reserved1: 0,
+            name_length,
+            name_hash,
+            reserved2: 0,
+            valid_data_length: 0,
+            reserved3: 0,
+            first_cluster: 0,
+            data_length: 0,
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use super::EntryType;
+
+/// The TexFAT Padding directory entry, which TexFAT uses to pad the first
+/// cluster of a directory. This crate does not implement TexFAT and only
+/// preserves it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TexFatPaddingEntry {
+    pub(super) entry_type: EntryType,
+    reserved: [u8; 31],
+}
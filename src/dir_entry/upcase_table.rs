@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use super::EntryType;
+
+/// The Up-case Table directory entry, which records where the Up-case Table
+/// is. It only ever appears in the root directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpcaseTableEntry {
+    pub(super) entry_type: EntryType,
+    reserved1: [u8; 3],
+    /// The checksum of the Up-case Table, see
+    /// [`crate::upcase_table::table_checksum`].
+    pub table_checksum: u32,
+    reserved2: [u8; 12],
+    /// The index of the first cluster of the cluster chain, as the FAT
+    /// describes, which hosts the Up-case Table.
+    pub first_cluster: u32,
+    /// The size of the Up-case Table in bytes.
+    pub data_length: u64,
+}
+
+impl UpcaseTableEntry {
+    pub fn new(table_checksum: u32, first_cluster: u32, length: u64) -> Self {
+        Self {
+            entry_type: EntryType::UPCASE_TABLE,
+            reserved1: [0; 3],
+            table_checksum,
+            reserved2: [0; 12],
+            first_cluster,
+            data_length: length,
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use uguid::Guid;
+
+use super::{EntryType, GeneralSecondaryFlags};
+
+/// The Vendor Allocation directory entry, a benign secondary entry pointing
+/// at clusters holding data of the vendor identified by its GUID.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VendorAllocationEntry {
+    pub(super) entry_type: EntryType,
+    pub general_secondary_flags: GeneralSecondaryFlags,
+    vendor_guid: [u8; 16],
+    pub vendor_defined: [u8; 2],
+    pub first_cluster: u32,
+    pub data_length: u64,
+}
+
+impl VendorAllocationEntry {
+    pub fn vendor_guid(&self) -> Guid {
+        Guid::from_bytes(self.vendor_guid)
+    }
+}
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use uguid::Guid;
+
+use super::{EntryType, GeneralSecondaryFlags};
+
+/// The Vendor Extension directory entry, a benign secondary entry carrying
+/// data of the vendor identified by its GUID.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VendorExtensionEntry {
+    pub(super) entry_type: EntryType,
+    general_secondary_flags: GeneralSecondaryFlags,
+    vendor_guid: [u8; 16],
+    pub vendor_defined: [u8; 14],
+}
+
+impl VendorExtensionEntry {
+    pub fn new(vendor_guid: Guid, vendor_defined: [u8; 14]) -> Self {
+        Self {
+            entry_type: EntryType::VENDOR_EXTENSION,
+            general_secondary_flags: GeneralSecondaryFlags::default(),
+            vendor_guid: vendor_guid.to_bytes(),
+            vendor_defined,
+        }
+    }
+
+    pub fn vendor_guid(&self) -> Guid {
+        Guid::from_bytes(self.vendor_guid)
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use uguid::Guid;
+
+use crate::error::Error;
+
+use super::{set_checksum, DirEntry, EntryType, GeneralPrimaryFlags};
+
+/// The Volume GUID directory entry, a benign primary entry which gives the
+/// volume a GUID. It only ever appears in the root directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeGuidEntry {
+    pub(super) entry_type: EntryType,
+    /// The entry has no secondary entries, so this is always 0.
+    secondary_count: u8,
+    set_checksum: u16,
+    general_primary_flags: GeneralPrimaryFlags,
+    volume_guid: [u8; 16],
+    reserved: [u8; 10],
+}
+
+impl VolumeGuidEntry {
+    pub fn new(volume_guid: Guid) -> Result<Self, Error> {
+        let mut entry = Self {
+            entry_type: EntryType::VOLUME_GUID,
+            secondary_count: 0,
+            set_checksum: 0,
+            general_primary_flags: GeneralPrimaryFlags::default(),
+            volume_guid: volume_guid.to_bytes(),
+            reserved: [0; 10],
+        };
+        entry.set_checksum =
+            set_checksum(&DirEntry::VolumeGuid(entry).to_bytes()?);
+        Ok(entry)
+    }
+
+    pub fn volume_guid(&self) -> Guid {
+        Guid::from_bytes(self.volume_guid)
+    }
+}
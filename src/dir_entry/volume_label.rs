@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::entry_set::INVALID_CHARACTERS;
+
+use super::{EntryType, Error};
+
+/// The Volume Label directory entry, which names the volume. It only ever
+/// appears in the root directory.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VolumeLabelEntry {
+    pub(super) entry_type: EntryType,
+    /// The number of UTF-16 code units of `volume_label` in use, at most 11.
+    character_count: u8,
+    volume_label: [u16; 11],
+    reserved: [u8; 8],
+}
+
+impl VolumeLabelEntry {
+    /// The most code units a volume label can hold.
+    pub const MAX_LENGTH: usize = 11;
+
+    /// Checks that `label` fits in the entry and only holds code units which
+    /// a file name could.
+    pub fn validate(label: &[u16]) -> Result<(), Error> {
+        if label.len() > Self::MAX_LENGTH {
+            return Err(Error::LabelTooLong(label.len()));
+        }
+        let invalid = label.iter().any(|unit| {
+            *unit < 0x20
+                || INVALID_CHARACTERS.iter().any(|c| *c as u16 == *unit)
+        });
+        if invalid {
+            return Err(Error::InvalidLabel);
+        }
+        Ok(())
+    }
+
+    /// A label of the first [`VolumeLabelEntry::MAX_LENGTH`] code units of
+    /// `label`.
+    pub fn new(label: &[u16]) -> Self {
+        let length = label.len().min(Self::MAX_LENGTH);
+        let mut volume_label = [0; Self::MAX_LENGTH];
+        volume_label[..length].copy_from_slice(&label[..length]);
+        Self {
+            entry_type: EntryType::VOLUME_LABEL,
+            character_count: length as u8,
+            volume_label,
+            reserved: [0; 8],
+        }
+    }
+
+    /// The label, which is empty when the volume has none.
+    pub fn label(&self) -> &[u16] {
+        let length = (self.character_count as usize).min(Self::MAX_LENGTH);
+        &self.volume_label[..length]
+    }
+}
@@ -0,0 +1,458 @@
+//! # Purpose
+//! Walks the clusters of a directory and yields the entry sets it holds,
+//! along with where on the volume each of them starts.
+
+use alloc::vec::Vec;
+
+use crate::{
+    block_device::BlockDevice,
+    cluster_heap::ClusterHeap,
+    dir_entry::{DirEntry, EntryType, FileEntry, TypeCategory},
+    entry_set::EntrySet,
+    error::Error,
+    fat::Fat,
+    stream::{self, Stream},
+};
+
+/// Where an entry is in its directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// The cluster holding the entry.
+    pub cluster: u32,
+    /// The index of the entry within `cluster`.
+    pub entry: u32,
+}
+
+impl Position {
+    /// The byte offset of the entry on the volume.
+    pub fn offset(&self, heap: ClusterHeap) -> u64 {
+        heap.cluster_offset(self.cluster)
+            + self.entry as u64 * DirEntry::SIZE as u64
+    }
+}
+
+/// Where the entry at `index` within a directory made up of `clusters` is.
+pub fn position_at(
+    heap: ClusterHeap,
+    clusters: &[u32],
+    index: u64,
+) -> Option<Position> {
+    let per_cluster = heap.bytes_per_cluster() / DirEntry::SIZE as u64;
+    Some(Position {
+        cluster: *clusters.get((index / per_cluster) as usize)?,
+        entry: (index % per_cluster) as u32,
+    })
+}
+
+/// Writes `bytes`, a whole number of entries, into `directory` from
+/// `position` on, continuing into the clusters which follow.
+pub fn write_entries(
+    heap: ClusterHeap,
+    fat: &Fat,
+    disk: &mut impl BlockDevice,
+    directory: Stream,
+    position: Position,
+    bytes: &[u8],
+) -> Result<(), Error> {
+    let clusters = directory.clusters(heap, fat, disk)?;
+    let per_cluster = heap.bytes_per_cluster() as usize / DirEntry::SIZE;
+    let first = clusters
+        .iter()
+        .position(|cluster| *cluster == position.cluster)
+        .unwrap_or(clusters.len())
+        * per_cluster
+        + position.entry as usize;
+    for (index, entry) in (first..).zip(bytes.chunks(DirEntry::SIZE)) {
+        let Some(cluster) = clusters.get(index / per_cluster) else {
+            return Err(stream::Error::TooShort {
+                data_length: ((index + 1) * DirEntry::SIZE) as u64,
+                clusters: clusters.len(),
+            }
+            .into());
+        };
+        let position = Position {
+            cluster: *cluster,
+            entry: (index % per_cluster) as u32,
+        };
+        disk.write_at(position.offset(heap), entry)?;
+    }
+    disk.flush()
+}
+
+/// What a directory holds at some position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    /// The entry set of a file or directory.
+    File(EntrySet),
+    /// Any other primary entry, such as the Allocation Bitmap or the Volume
+    /// Label of the root directory.
+    Other(DirEntry),
+}
+
+impl Entry {
+    pub fn in_use(&self) -> bool {
+        match self {
+            Entry::File(set) => set.file.entry_type().in_use(),
+            Entry::Other(entry) => entry.entry_type().in_use(),
+        }
+    }
+}
+
+/// Iterates over the entries of a directory until its end, the first entry
+/// of type 00h.
+///
+/// Entry sets which are not in use are skipped unless asked for with
+/// [`Entries::include_deleted`], and even then those which have been partly
+/// overwritten are. A malformed entry set in use is reported and iteration
+/// goes on after it, while an error reading the volume ends the iteration.
+pub struct Entries<'a, Disk> {
+    heap: ClusterHeap,
+    disk: &'a mut Disk,
+    clusters: Vec<u32>,
+    /// The index of the next entry within the whole directory.
+    next: u64,
+    /// The index of the cluster in `buffer` within `clusters`.
+    buffered: Option<usize>,
+    buffer: Vec<u8>,
+    include_deleted: bool,
+    done: bool,
+}
+
+impl<'a, Disk: BlockDevice> Entries<'a, Disk> {
+    /// Lists the entries of the directory allocated at `directory`.
+    pub fn new(
+        heap: ClusterHeap,
+        fat: &Fat,
+        disk: &'a mut Disk,
+        directory: Stream,
+    ) -> Result<Self, Error> {
+        let clusters = directory.clusters(heap, fat, disk)?;
+        Ok(Self {
+            heap,
+            disk,
+            clusters,
+            next: 0,
+            buffered: None,
+            buffer: alloc::vec![0; heap.bytes_per_cluster() as usize],
+            include_deleted: false,
+            done: false,
+        })
+    }
+
+    /// Also yield the entry sets of deleted files.
+    pub fn include_deleted(mut self, include_deleted: bool) -> Self {
+        self.include_deleted = include_deleted;
+        self
+    }
+
+    fn entries_per_cluster(&self) -> u64 {
+        self.heap.bytes_per_cluster() / DirEntry::SIZE as u64
+    }
+
+    fn position_of(&self, index: u64) -> Option<Position> {
+        position_at(self.heap, &self.clusters, index)
+    }
+
+    /// Finds the first run of `count` entries in a row which are not in use,
+    /// counting every entry after the end of the directory as unused.
+    ///
+    /// Returns the index of the first entry of the run within the directory
+    /// and the length of the run. The run is only shorter than `count` when
+    /// it is cut off by the last cluster, so the directory has to grow to
+    /// hold the rest of it.
+    pub(crate) fn find_free(mut self, count: u64) -> Result<(u64, u64), Error> {
+        let total = self.clusters.len() as u64 * self.entries_per_cluster();
+        let mut start = 0;
+        let mut length = 0;
+        let mut index = 0;
+        while let Some(slot) = self.slot(index)? {
+            let entry_type = EntryType::from_byte(slot[0]);
+            if entry_type == EntryType::END_OF_DIRECTORY {
+                if length == 0 {
+                    start = index;
+                }
+                length += total - index;
+                return Ok((start, length.min(count)));
+            }
+            if entry_type.in_use() {
+                length = 0;
+            } else {
+                if length == 0 {
+                    start = index;
+                }
+                length += 1;
+                if length == count {
+                    return Ok((start, length));
+                }
+            }
+            index += 1;
+        }
+        if length == 0 {
+            start = total;
+        }
+        Ok((start, length))
+    }
+
+    /// Reads the entry at `index` within the directory, or `None` past the
+    /// last cluster of the directory.
+    fn slot(&mut self, index: u64) -> Result<Option<&[u8]>, Error> {
+        let cluster = (index / self.entries_per_cluster()) as usize;
+        let Some(position) = self.position_of(index) else {
+            return Ok(None);
+        };
+        if self.buffered != Some(cluster) {
+            self.buffered = None;
+            self.disk.read_at(
+                self.heap.cluster_offset(position.cluster),
+                &mut self.buffer,
+            )?;
+            self.buffered = Some(cluster);
+        }
+        let start = position.entry as usize * DirEntry::SIZE;
+        Ok(Some(&self.buffer[start..start + DirEntry::SIZE]))
+    }
+
+    /// Reads the entry set whose File entry is at `index`, returning it and
+    /// the index of the entry after it.
+    ///
+    /// Collection stops early at an entry which cannot belong to the set, so
+    /// that a truncated set does not swallow the entries after it.
+    fn entry_set(
+        &mut self,
+        index: u64,
+        file: &FileEntry,
+    ) -> Result<(Result<EntrySet, Error>, u64), Error> {
+        let in_use = file.entry_type().in_use();
+        let mut bytes =
+            Vec::with_capacity(EntrySet::len_of(file) * DirEntry::SIZE);
+        let mut end = index;
+        while end < index + EntrySet::len_of(file) as u64 {
+            let Some(slot) = self.slot(end)? else {
+                break;
+            };
+            let entry_type = EntryType::from_byte(slot[0]);
+            if end > index
+                && (entry_type.category() == TypeCategory::Primary
+                    || entry_type.in_use() != in_use)
+            {
+                break;
+            }
+            bytes.extend_from_slice(slot);
+            end += 1;
+        }
+        Ok((EntrySet::parse(&bytes), end))
+    }
+
+    fn try_next(&mut self) -> Result<Option<(Position, Entry)>, Error> {
+        loop {
+            let index = self.next;
+            let Some(position) = self.position_of(index) else {
+                return Ok(None);
+            };
+            let Some(slot) = self.slot(index)? else {
+                return Ok(None);
+            };
+            let entry = DirEntry::parse(slot);
+            self.next += 1;
+            let entry = entry?;
+            let in_use = entry.entry_type().in_use();
+            match entry {
+                DirEntry::EndOfDirectory => return Ok(None),
+                DirEntry::File(file) => {
+                    if !in_use && !self.include_deleted {
+                        continue;
+                    }
+                    let (set, end) = self.entry_set(index, &file)?;
+                    self.next = end;
+                    match set {
+                        Ok(set) => {
+                            return Ok(Some((position, Entry::File(set))))
+                        }
+                        Err(e) if in_use => return Err(e),
+                        Err(_) => {}
+                    }
+                }
+                entry
+                    if in_use
+                        && entry.entry_type().category()
+                            == TypeCategory::Primary =>
+                {
+                    return Ok(Some((position, Entry::Other(entry))));
+                }
+                // deleted entries, and secondary entries without their
+                // primary entry
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<Disk: BlockDevice> Iterator for Entries<'_, Disk> {
+    type Item = Result<(Position, Entry), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.try_next() {
+            Ok(Some(item)) => Some(Ok(item)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = e.is_device_error();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        block_device::BlockDevice,
+        dir_entry::{DirEntry, FileAttributes},
+        entry_set::EntrySet,
+        stream::Stream,
+        test_util::{format, utf16},
+        FileSystem,
+    };
+
+    use super::{Entry, Position};
+
+    fn entry_set(fs: &FileSystem<impl BlockDevice>, name: &str) -> EntrySet {
+        EntrySet::new(&utf16(name), FileAttributes::empty(), fs.upcase_table())
+            .unwrap()
+    }
+
+    /// Writes `bytes` as the entries starting at `index` of a directory
+    /// made of `clusters`.
+    fn write_entries(
+        fs: &mut FileSystem<impl BlockDevice>,
+        clusters: &[u32],
+        index: usize,
+        bytes: &[u8],
+    ) {
+        let heap = fs.cluster_heap();
+        let per_cluster = heap.bytes_per_cluster() as usize / DirEntry::SIZE;
+        for (i, entry) in bytes.chunks(DirEntry::SIZE).enumerate() {
+            let position = Position {
+                cluster: clusters[(index + i) / per_cluster],
+                entry: ((index + i) % per_cluster) as u32,
+            };
+            let disk = fs.boot_region_mut().disk_mut();
+            disk.write_at(position.offset(heap), entry).unwrap();
+        }
+    }
+
+    #[test]
+    pub fn list_root() {
+        let mut fs = format();
+        let root = fs.root_directory();
+        let clusters = [root.first_cluster, root.first_cluster + 1];
+        let fat = *fs.fat();
+        fat.set_chain(fs.boot_region_mut().disk_mut(), clusters)
+            .unwrap();
+
+        let mut first = entry_set(&fs, "first file");
+        write_entries(&mut fs, &clusters, 2, &first.to_bytes().unwrap());
+        let mut deleted = entry_set(&fs, "deleted");
+        let mut bytes = deleted.to_bytes().unwrap();
+        for entry in bytes.chunks_mut(DirEntry::SIZE) {
+            entry[0] &= 0x7F;
+        }
+        let deleted = EntrySet::parse(&bytes).unwrap();
+        write_entries(&mut fs, &clusters, 5, &bytes);
+        // entries which were never in use are skipped as well
+        let unused = [0x05; DirEntry::SIZE].repeat(125 - 8);
+        write_entries(&mut fs, &clusters, 8, &unused);
+        // a name of 40 code units takes up 3 File Name entries, and the set
+        // crosses into the second cluster
+        let mut spanning = entry_set(&fs, &"spanning".repeat(5));
+        write_entries(&mut fs, &clusters, 125, &spanning.to_bytes().unwrap());
+        let mut corrupt = entry_set(&fs, "corrupt").to_bytes().unwrap();
+        corrupt[DirEntry::SIZE + 8] ^= 1;
+        write_entries(&mut fs, &clusters, 130, &corrupt);
+        let mut last = entry_set(&fs, "last");
+        write_entries(&mut fs, &clusters, 133, &last.to_bytes().unwrap());
+
+        let entries: Vec<_> = fs.entries(root).unwrap().collect();
+        assert_eq!(entries.len(), 6);
+        assert!(matches!(
+            entries[0],
+            Ok((_, Entry::Other(DirEntry::AllocationBitmap(_))))
+        ));
+        assert!(matches!(
+            entries[1],
+            Ok((_, Entry::Other(DirEntry::UpcaseTable(_))))
+        ));
+        let position = |cluster, entry| Position { cluster, entry };
+        assert_eq!(
+            *entries[2].as_ref().unwrap(),
+            (position(clusters[0], 2), Entry::File(first))
+        );
+        assert_eq!(
+            *entries[3].as_ref().unwrap(),
+            (position(clusters[0], 125), Entry::File(spanning))
+        );
+        assert!(entries[4].is_err());
+        assert_eq!(
+            *entries[5].as_ref().unwrap(),
+            (position(clusters[1], 5), Entry::File(last))
+        );
+
+        let entries: Vec<_> = fs
+            .entries(root)
+            .unwrap()
+            .include_deleted(true)
+            .filter_map(Result::ok)
+            .collect();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[3],
+            (position(clusters[0], 5), Entry::File(deleted))
+        );
+        assert!(!entries[3].1.in_use());
+    }
+
+    #[test]
+    pub fn contiguous_directory() {
+        let mut fs = format();
+        let first_cluster = fs.root_directory().first_cluster + 10;
+        let clusters = [first_cluster, first_cluster + 1];
+        let mut set = entry_set(&fs, "contiguous");
+        let unused = [0x05; DirEntry::SIZE].repeat(127);
+        write_entries(&mut fs, &clusters, 0, &unused);
+        write_entries(&mut fs, &clusters, 127, &set.to_bytes().unwrap());
+
+        // the FAT entries of the clusters are free, so only NoFatChain
+        // finds the second cluster
+        let directory = Stream {
+            first_cluster,
+            data_length: 2 * fs.cluster_heap().bytes_per_cluster(),
+            no_fat_chain: true,
+        };
+        let entries: Vec<_> = fs
+            .entries(directory)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            entries,
+            [(
+                Position {
+                    cluster: first_cluster,
+                    entry: 127
+                },
+                Entry::File(set)
+            )]
+        );
+        assert!(fs
+            .entries(Stream {
+                no_fat_chain: false,
+                ..directory
+            })
+            .is_err());
+    }
+}
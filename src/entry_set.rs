@@ -0,0 +1,576 @@
+//! # Purpose
+//! Assembles and takes apart the directory entry set describing a file or
+//! directory: a File entry followed by a Stream Extension entry, 1 through 17
+//! File Name entries and possibly further benign secondary entries.
+
+use core::fmt::Display;
+
+use alloc::vec::Vec;
+
+use crate::{
+    dir_entry::{
+        set_checksum, DirEntry, EntryType, FileAttributes, FileEntry,
+        FileNameEntry, StreamExtensionEntry, TypeCategory, TypeImportance,
+    },
+    error::Error as CrateError,
+    upcase_table::UpcaseTable,
+};
+
+/// Ways an entry set can be malformed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The directory ended `found` entries into a set of `expected`.
+    Truncated {
+        expected: usize,
+        found: usize,
+    },
+    /// The first entry of the set is not a File entry.
+    NotAFile(EntryType),
+    /// The SecondaryCount of the File entry is outside of 2 through 18.
+    SecondaryCount(u8),
+    /// The entry at `index` of the set should have been of type `expected`.
+    UnexpectedEntry {
+        index: usize,
+        expected: EntryType,
+        found: EntryType,
+    },
+    /// The set holds a critical secondary entry this crate does not know, so
+    /// it cannot be used.
+    UnknownCriticalEntry {
+        index: usize,
+        found: EntryType,
+    },
+    /// The entry at `index` of the set is a File Name entry beyond those the
+    /// NameLength of the Stream Extension entry needs.
+    SurplusFileName {
+        index: usize,
+    },
+    /// The set would take up more than the 19 entries a set can have.
+    TooManyEntries(usize),
+    /// The NameLength of the Stream Extension entry is 0, or needs more File
+    /// Name entries than the set has.
+    NameLength {
+        name_length: u8,
+        file_names: usize,
+    },
+    ChecksumMismatch {
+        expected: u16,
+        calculated: u16,
+    },
+    NameHashMismatch {
+        expected: u16,
+        calculated: u16,
+    },
+    /// A name is empty, longer than 255 code units, is "." or "..", or
+    /// contains a code unit which is not allowed in names.
+    InvalidName,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Truncated { expected, found } => write!(
+                f,
+                "The directory ends {} entries into an entry set of {}.",
+                found, expected
+            ),
+            Error::NotAFile(found) => write!(
+                f,
+                "Entry set starts with entry type {:#04x} rather than a file \
+                entry.",
+                found.to_byte()
+            ),
+            Error::SecondaryCount(count) => write!(
+                f,
+                "A file entry cannot have {} secondary entries.",
+                count
+            ),
+            Error::UnexpectedEntry {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Entry {} of the entry set has type {:#04x} rather than \
+                {:#04x}.",
+                index,
+                found.to_byte(),
+                expected.to_byte()
+            ),
+            Error::UnknownCriticalEntry { index, found } => write!(
+                f,
+                "Entry {} of the entry set has unknown critical type {:#04x}.",
+                index,
+                found.to_byte()
+            ),
+            Error::SurplusFileName { index } => write!(
+                f,
+                "Entry {} of the entry set is a file name entry the name \
+                length does not need.",
+                index
+            ),
+            Error::TooManyEntries(len) => write!(
+                f,
+                "An entry set of {} entries is longer than the {} entries a \
+                set can have.",
+                len,
+                EntrySet::MAX_LEN
+            ),
+            Error::NameLength {
+                name_length,
+                file_names,
+            } => write!(
+                f,
+                "A name of {} code units does not fit in {} file name \
+                entries.",
+                name_length, file_names
+            ),
+            Error::ChecksumMismatch {
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "Set checksum {:#06x} does not match the calculated checksum \
+                {:#06x}.",
+                expected, calculated
+            ),
+            Error::NameHashMismatch {
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "Name hash {:#06x} does not match the calculated hash {:#06x}.",
+                expected, calculated
+            ),
+            Error::InvalidName => write!(f, "The name is not a valid name."),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The longest name in UTF-16 code units.
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Code units below 20h and these may not appear in names, nor in volume
+/// labels.
+pub(crate) const INVALID_CHARACTERS: [u8; 9] = *b"\"*/:<>?\\|";
+
+/// Checks that `name` can name a file or directory.
+pub fn validate_name(name: &[u16]) -> Result<(), Error> {
+    let dot = b'.' as u16;
+    let reserved = name == [dot] || name == [dot, dot];
+    let invalid = name.iter().any(|unit| {
+        *unit < 0x20 || INVALID_CHARACTERS.iter().any(|c| *c as u16 == *unit)
+    });
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH || reserved || invalid {
+        return Err(Error::InvalidName);
+    }
+    Ok(())
+}
+
+/// Calculates the NameHash of a name: a checksum over the up-cased name,
+/// every code unit fed in low byte first.
+pub fn name_hash(name: &[u16], upcase_table: &UpcaseTable) -> u16 {
+    name.iter()
+        .flat_map(|unit| upcase_table.upcase(*unit).to_le_bytes())
+        .fold(0u16, |hash, byte| {
+            hash.rotate_right(1).wrapping_add(byte as u16)
+        })
+}
+
+/// The entry set of a file or directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntrySet {
+    pub file: FileEntry,
+    pub stream_extension: StreamExtensionEntry,
+    name: Vec<u16>,
+    /// Benign secondary entries following the File Name entries, which are
+    /// kept as they are.
+    pub extra: Vec<DirEntry>,
+}
+
+impl EntrySet {
+    /// The most entries a set can have: a File entry and 18 secondary
+    /// entries.
+    pub const MAX_LEN: usize = 19;
+
+    /// The entry set of a new, empty file named `name`.
+    pub fn new(
+        name: &[u16],
+        file_attributes: FileAttributes,
+        upcase_table: &UpcaseTable,
+    ) -> Result<Self, Error> {
+        validate_name(name)?;
+        let mut set = Self {
+            file: FileEntry::new(0, file_attributes),
+            stream_extension: StreamExtensionEntry::new(0, 0),
+            name: Vec::new(),
+            extra: Vec::new(),
+        };
+        set.set_name(name, upcase_table)?;
+        Ok(set)
+    }
+
+    /// The number of entries in a set whose File entry is `file`.
+    pub fn len_of(file: &FileEntry) -> usize {
+        1 + file.secondary_count as usize
+    }
+
+    /// The number of entries the set takes up in its directory.
+    pub fn len(&self) -> usize {
+        2 + Self::file_names(self.name.len()) + self.extra.len()
+    }
+
+    /// An entry set always has at least a File and a Stream Extension entry.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// The number of File Name entries a name of `length` code units needs.
+    fn file_names(length: usize) -> usize {
+        length.div_ceil(FileNameEntry::LENGTH)
+    }
+
+    pub fn name(&self) -> &[u16] {
+        &self.name
+    }
+
+    /// Renames the file, updating the NameLength and NameHash.
+    pub fn set_name(
+        &mut self,
+        name: &[u16],
+        upcase_table: &UpcaseTable,
+    ) -> Result<(), Error> {
+        validate_name(name)?;
+        let len = 2 + Self::file_names(name.len()) + self.extra.len();
+        if len > Self::MAX_LEN {
+            return Err(Error::TooManyEntries(len));
+        }
+        self.name = name.to_vec();
+        self.stream_extension.name_length = name.len() as u8;
+        self.stream_extension.name_hash = name_hash(name, upcase_table);
+        self.file.secondary_count = (self.len() - 1) as u8;
+        Ok(())
+    }
+
+    /// Checks the NameHash of the set against its name.
+    pub fn verify_name_hash(
+        &self,
+        upcase_table: &UpcaseTable,
+    ) -> Result<(), Error> {
+        let calculated = name_hash(&self.name, upcase_table);
+        if calculated != self.stream_extension.name_hash {
+            return Err(Error::NameHashMismatch {
+                expected: self.stream_extension.name_hash,
+                calculated,
+            });
+        }
+        Ok(())
+    }
+
+    /// Parses an entry set from its entries as they are stored on disk,
+    /// verifying its structure and SetChecksum.
+    pub fn parse(bytes: &[u8]) -> Result<Self, CrateError> {
+        let found = bytes.len() / DirEntry::SIZE;
+        let entry =
+            |index: usize| DirEntry::parse(&bytes[index * DirEntry::SIZE..]);
+        if found == 0 {
+            return Err(Error::Truncated { expected: 1, found }.into());
+        }
+        let file = match entry(0)? {
+            DirEntry::File(file) => file,
+            other => return Err(Error::NotAFile(other.entry_type()).into()),
+        };
+        if !(2..=18).contains(&file.secondary_count) {
+            return Err(Error::SecondaryCount(file.secondary_count).into());
+        }
+        let expected = Self::len_of(&file);
+        if found < expected {
+            return Err(Error::Truncated { expected, found }.into());
+        }
+        let bytes = &bytes[..expected * DirEntry::SIZE];
+        let calculated = if file.entry_type().in_use() {
+            set_checksum(bytes)
+        } else {
+            // the checksum of a deleted set was calculated while it was in
+            // use
+            let mut in_use = bytes.to_vec();
+            for entry in in_use.chunks_mut(DirEntry::SIZE) {
+                entry[0] =
+                    EntryType::from_byte(entry[0]).with_in_use(true).to_byte();
+            }
+            set_checksum(&in_use)
+        };
+        if calculated != file.set_checksum {
+            return Err(Error::ChecksumMismatch {
+                expected: file.set_checksum,
+                calculated,
+            }
+            .into());
+        }
+
+        let stream_extension = match entry(1)? {
+            DirEntry::StreamExtension(stream_extension) => stream_extension,
+            other => {
+                return Err(Error::UnexpectedEntry {
+                    index: 1,
+                    expected: EntryType::STREAM_EXTENSION,
+                    found: other.entry_type(),
+                }
+                .into())
+            }
+        };
+        let name_length = stream_extension.name_length;
+        let file_names = Self::file_names(name_length as usize);
+        let available = (2..expected)
+            .take_while(|index| {
+                matches!(entry(*index), Ok(DirEntry::FileName(_)))
+            })
+            .count();
+        if name_length == 0 || file_names > available {
+            return Err(Error::NameLength {
+                name_length,
+                file_names: available,
+            }
+            .into());
+        }
+        let mut name = Vec::with_capacity(file_names * FileNameEntry::LENGTH);
+        for index in 2..2 + file_names {
+            if let DirEntry::FileName(file_name) = entry(index)? {
+                name.extend_from_slice(&file_name.file_name);
+            }
+        }
+        name.truncate(name_length as usize);
+
+        let mut extra = Vec::new();
+        for index in 2 + file_names..expected {
+            let secondary = entry(index)?;
+            if let DirEntry::FileName(_) = secondary {
+                return Err(Error::SurplusFileName { index }.into());
+            }
+            let entry_type = secondary.entry_type();
+            if entry_type.category() != TypeCategory::Secondary {
+                return Err(Error::UnexpectedEntry {
+                    index,
+                    expected: EntryType::VENDOR_EXTENSION,
+                    found: entry_type,
+                }
+                .into());
+            }
+            if entry_type.importance() == TypeImportance::Critical {
+                return Err(Error::UnknownCriticalEntry {
+                    index,
+                    found: entry_type,
+                }
+                .into());
+            }
+            extra.push(secondary);
+        }
+        Ok(Self {
+            file,
+            stream_extension,
+            name,
+            extra,
+        })
+    }
+
+    /// The entries of the set as they are stored on disk, with the
+    /// SetChecksum of the File entry updated.
+    pub fn to_bytes(&mut self) -> Result<Vec<u8>, CrateError> {
+        if self.len() > Self::MAX_LEN {
+            return Err(Error::TooManyEntries(self.len()).into());
+        }
+        self.file.secondary_count = (self.len() - 1) as u8;
+        let mut entries = Vec::with_capacity(self.len());
+        entries.push(DirEntry::File(self.file));
+        entries.push(DirEntry::StreamExtension(self.stream_extension));
+        for chunk in self.name.chunks(FileNameEntry::LENGTH) {
+            let mut file_name = [0; FileNameEntry::LENGTH];
+            file_name[..chunk.len()].copy_from_slice(chunk);
+            entries.push(DirEntry::FileName(FileNameEntry::new(file_name)));
+        }
+        entries.extend_from_slice(&self.extra);
+        let mut bytes = Vec::with_capacity(entries.len() * DirEntry::SIZE);
+        for entry in &entries {
+            bytes.extend_from_slice(&entry.to_bytes()?);
+        }
+        self.file.set_checksum = set_checksum(&bytes);
+        bytes[2..4].copy_from_slice(&self.file.set_checksum.to_le_bytes());
+        Ok(bytes)
+    }
+
+    /// The entries of the set with InUse cleared, as a deleted set is left in
+    /// its directory. The SetChecksum stays that of the set in use.
+    pub fn to_deleted_bytes(&mut self) -> Result<Vec<u8>, CrateError> {
+        let mut bytes = self.to_bytes()?;
+        for entry in bytes.chunks_mut(DirEntry::SIZE) {
+            entry[0] =
+                EntryType::from_byte(entry[0]).with_in_use(false).to_byte();
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::{
+        dir_entry::{
+            DirEntry, EntryType, FileAttributes, VendorExtensionEntry,
+        },
+        error::Error as CrateError,
+        test_util::utf16,
+        upcase_table::UpcaseTable,
+    };
+
+    use super::{name_hash, EntrySet, Error, MAX_NAME_LENGTH};
+
+    fn set_error(result: Result<EntrySet, CrateError>) -> Error {
+        match result {
+            Err(CrateError::EntrySet(e)) => e,
+            Err(e) => panic!("{}", e),
+            Ok(_) => panic!("the entry set should be rejected"),
+        }
+    }
+
+    #[test]
+    pub fn names() {
+        let upcase_table = UpcaseTable::generate();
+        for length in [1, 14, 15, 16, 30, 31, 254, MAX_NAME_LENGTH] {
+            let name: Vec<u16> =
+                (0..length).map(|i| b'a' as u16 + (i % 26) as u16).collect();
+            let mut set =
+                EntrySet::new(&name, FileAttributes::ARCHIVE, &upcase_table)
+                    .unwrap();
+            assert_eq!(set.len(), 2 + length.div_ceil(15));
+            let bytes = set.to_bytes().unwrap();
+            assert_eq!(bytes.len(), set.len() * DirEntry::SIZE);
+            let parsed = EntrySet::parse(&bytes).unwrap();
+            assert_eq!(parsed.name(), name);
+            assert_eq!(parsed, set);
+            parsed.verify_name_hash(&upcase_table).unwrap();
+        }
+        for name in ["", ".", "..", "a/b", "a:b", "tab\t", "what?"] {
+            assert_eq!(
+                EntrySet::new(
+                    &utf16(name),
+                    FileAttributes::empty(),
+                    &upcase_table
+                ),
+                Err(Error::InvalidName)
+            );
+        }
+        let too_long = [b'a' as u16; MAX_NAME_LENGTH + 1];
+        assert_eq!(
+            EntrySet::new(&too_long, FileAttributes::empty(), &upcase_table),
+            Err(Error::InvalidName)
+        );
+        // hashes are computed from the up-cased name
+        assert_eq!(
+            name_hash(&utf16("ReadMe.txt"), &upcase_table),
+            name_hash(&utf16("README.TXT"), &upcase_table)
+        );
+    }
+
+    #[test]
+    pub fn malformed_sets() {
+        let upcase_table = UpcaseTable::generate();
+        let mut set = EntrySet::new(
+            &utf16("a file name, 20 long"),
+            FileAttributes::empty(),
+            &upcase_table,
+        )
+        .unwrap();
+        set.extra
+            .push(DirEntry::VendorExtension(VendorExtensionEntry::new(
+                uguid::Guid::ZERO,
+                [1; 14],
+            )));
+        let bytes = set.to_bytes().unwrap();
+        assert_eq!(EntrySet::parse(&bytes).unwrap(), set);
+
+        assert_eq!(
+            set_error(EntrySet::parse(&bytes[..3 * DirEntry::SIZE])),
+            Error::Truncated {
+                expected: 5,
+                found: 3
+            }
+        );
+        assert_eq!(
+            set_error(EntrySet::parse(&bytes[DirEntry::SIZE..])),
+            Error::NotAFile(EntryType::STREAM_EXTENSION)
+        );
+
+        let mut corrupt = bytes.clone();
+        corrupt[2 * DirEntry::SIZE + 2] ^= 1;
+        assert!(matches!(
+            set_error(EntrySet::parse(&corrupt)),
+            Error::ChecksumMismatch { .. }
+        ));
+
+        // a name needing three File Name entries when there are two
+        let mut long_name = set.clone();
+        long_name.stream_extension.name_length = 31;
+        let bytes = long_name.to_bytes().unwrap();
+        assert_eq!(
+            set_error(EntrySet::parse(&bytes)),
+            Error::NameLength {
+                name_length: 31,
+                file_names: 2
+            }
+        );
+
+        // a critical secondary entry this crate does not know
+        let mut unknown = set.clone();
+        let mut raw = [0u8; DirEntry::SIZE];
+        raw[0] = 0xC5;
+        unknown.extra = alloc::vec![DirEntry::parse(&raw).unwrap()];
+        let bytes = unknown.to_bytes().unwrap();
+        assert_eq!(
+            set_error(EntrySet::parse(&bytes)),
+            Error::UnknownCriticalEntry {
+                index: 4,
+                found: EntryType::from_byte(0xC5)
+            }
+        );
+
+        // a name needing one File Name entry when there are two
+        let mut short_name = set.clone();
+        short_name.stream_extension.name_length = 15;
+        let bytes = short_name.to_bytes().unwrap();
+        assert_eq!(
+            set_error(EntrySet::parse(&bytes)),
+            Error::SurplusFileName { index: 3 }
+        );
+
+        // a Stream Extension, 2 File Name and 15 benign entries make the 18
+        // secondary entries a set can have
+        let mut full = set.clone();
+        full.extra = alloc::vec![set.extra[0]; 15];
+        assert_eq!(full.len(), EntrySet::MAX_LEN);
+        let bytes = full.to_bytes().unwrap();
+        assert_eq!(EntrySet::parse(&bytes).unwrap(), full);
+        assert_eq!(
+            full.set_name(
+                &utf16("a longer name, 31 code units..."),
+                &upcase_table
+            ),
+            Err(Error::TooManyEntries(20))
+        );
+        assert_eq!(full.name(), set.name());
+        full.extra.push(set.extra[0]);
+        assert!(matches!(
+            full.to_bytes(),
+            Err(CrateError::EntrySet(Error::TooManyEntries(20)))
+        ));
+
+        let mut bad_hash = set.clone();
+        bad_hash.stream_extension.name_hash ^= 1;
+        assert!(matches!(
+            bad_hash.verify_name_hash(&upcase_table),
+            Err(Error::NameHashMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+use core::fmt::Display;
+
+use bincode::error::{DecodeError, EncodeError};
+
+use crate::{
+    allocation_bitmap, dir_entry, entry_set, fat, file, path, stream,
+    super_block::{extended_boot_sector::index, BoundError},
+    time, upcase_table,
+};
+
+/// Every error this crate can produce.
+#[derive(Debug)]
+pub enum Error {
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// A [`crate::BlockDevice`] failed to transfer sectors, for reasons given
+    /// by its implementation.
+    Device(&'static str),
+    Encode(EncodeError),
+    Decode(DecodeError),
+    Bound(BoundError),
+    Index(index::Error),
+    Fat(fat::Error),
+    AllocationBitmap(allocation_bitmap::Error),
+    UpcaseTable(upcase_table::Error),
+    DirEntry(dir_entry::Error),
+    EntrySet(entry_set::Error),
+    Path(path::Error),
+    Stream(stream::Error),
+    File(file::Error),
+    Time(time::Error),
+    /// The FileSystemName field of the boot sector is not "EXFAT   ".
+    NotExfat,
+    /// The sector at the given volume-relative index lacks its boot
+    /// signature.
+    InvalidSignature {
+        sector: u64,
+    },
+    /// The Boot Checksum sector does not match the checksum of the
+    /// preceding 11 sectors.
+    ChecksumMismatch {
+        expected: u32,
+        calculated: u32,
+    },
+    /// A field which has to be a power of two is not.
+    NotPowerOfTwo {
+        field: &'static str,
+        value: usize,
+    },
+    /// Boot code is longer than the space its sector reserves for it.
+    BootCodeTooLong {
+        length: usize,
+        max: usize,
+    },
+    /// There are only 10 OEM parameters.
+    ParameterIndex(usize),
+    /// A buffer handed to a [`crate::BlockDevice`] does not span a whole
+    /// number of sectors.
+    UnalignedBuffer {
+        len: usize,
+        sector_size: usize,
+    },
+    /// A sector past the end of a [`crate::BlockDevice`] was accessed.
+    OutOfRange {
+        sector: u64,
+        sector_count: u64,
+    },
+    /// A boot region held in memory is shorter than the 12 sectors its boot
+    /// sector calls for.
+    BootRegionTooShort {
+        len: usize,
+        required: usize,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Device(e) => write!(f, "Device error: {}", e),
+            Error::Encode(e) => write!(f, "Serialization error: {}", e),
+            Error::Decode(e) => write!(f, "Deserialization error: {}", e),
+            Error::Bound(e) => write!(f, "{}", e),
+            Error::Index(e) => write!(f, "{}", e),
+            Error::Fat(e) => write!(f, "{}", e),
+            Error::AllocationBitmap(e) => write!(f, "{}", e),
+            Error::UpcaseTable(e) => write!(f, "{}", e),
+            Error::DirEntry(e) => write!(f, "{}", e),
+            Error::EntrySet(e) => write!(f, "{}", e),
+            Error::Path(e) => write!(f, "{}", e),
+            Error::Stream(e) => write!(f, "{}", e),
+            Error::File(e) => write!(f, "{}", e),
+            Error::Time(e) => write!(f, "{}", e),
+            Error::NotExfat => {
+                write!(f, "The volume is not formatted as exFAT.")
+            }
+            Error::InvalidSignature { sector } => {
+                write!(f, "Sector {} has an invalid boot signature.", sector)
+            }
+            Error::ChecksumMismatch {
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "Boot checksum {:#010x} does not match the calculated \
+                checksum {:#010x}.",
+                expected, calculated
+            ),
+            Error::NotPowerOfTwo { field, value } => {
+                write!(f, "Field {} = {} is not a power of two", field, value)
+            }
+            Error::BootCodeTooLong { length, max } => write!(
+                f,
+                "Boot code of {} bytes is longer than the {} bytes available.",
+                length, max
+            ),
+            Error::ParameterIndex(index) => write!(
+                f,
+                "OEM parameter index {} is too big. There are only 10 \
+                parameters.",
+                index
+            ),
+            Error::UnalignedBuffer { len, sector_size } => write!(
+                f,
+                "A buffer of {} bytes is not a multiple of the {} byte \
+                sector size.",
+                len, sector_size
+            ),
+            Error::OutOfRange {
+                sector,
+                sector_count,
+            } => write!(
+                f,
+                "Sector {} is out of range of a device with {} sectors.",
+                sector, sector_count
+            ),
+            Error::BootRegionTooShort { len, required } => write!(
+                f,
+                "A boot region of {} bytes is shorter than the {} bytes of \
+                its 12 sectors.",
+                len, required
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl Error {
+    /// Whether the error was reported by the underlying device rather than
+    /// found in the data it holds.
+    pub(crate) fn is_device_error(&self) -> bool {
+        match self {
+            #[cfg(feature = "std")]
+            Error::Io(_) => true,
+            Error::Device(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(value: EncodeError) -> Self {
+        Self::Encode(value)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(value: DecodeError) -> Self {
+        Self::Decode(value)
+    }
+}
+
+impl From<BoundError> for Error {
+    fn from(value: BoundError) -> Self {
+        Self::Bound(value)
+    }
+}
+
+impl From<index::Error> for Error {
+    fn from(value: index::Error) -> Self {
+        Self::Index(value)
+    }
+}
+
+impl From<fat::Error> for Error {
+    fn from(value: fat::Error) -> Self {
+        Self::Fat(value)
+    }
+}
+
+impl From<allocation_bitmap::Error> for Error {
+    fn from(value: allocation_bitmap::Error) -> Self {
+        Self::AllocationBitmap(value)
+    }
+}
+
+impl From<upcase_table::Error> for Error {
+    fn from(value: upcase_table::Error) -> Self {
+        Self::UpcaseTable(value)
+    }
+}
+
+impl From<dir_entry::Error> for Error {
+    fn from(value: dir_entry::Error) -> Self {
+        Self::DirEntry(value)
+    }
+}
+
+impl From<entry_set::Error> for Error {
+    fn from(value: entry_set::Error) -> Self {
+        Self::EntrySet(value)
+    }
+}
+
+impl From<path::Error> for Error {
+    fn from(value: path::Error) -> Self {
+        Self::Path(value)
+    }
+}
+
+impl From<stream::Error> for Error {
+    fn from(value: stream::Error) -> Self {
+        Self::Stream(value)
+    }
+}
+
+impl From<file::Error> for Error {
+    fn from(value: file::Error) -> Self {
+        Self::File(value)
+    }
+}
+
+impl From<time::Error> for Error {
+    fn from(value: time::Error) -> Self {
+        Self::Time(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(value: Error) -> Self {
+        use std::io::ErrorKind;
+        match value {
+            Error::Io(e) => e,
+            Error::Path(path::Error::NotFound) => ErrorKind::NotFound.into(),
+            Error::Path(path::Error::NotADirectory) => {
+                ErrorKind::NotADirectory.into()
+            }
+            Error::Path(path::Error::IsADirectory) => {
+                ErrorKind::IsADirectory.into()
+            }
+            Error::Path(path::Error::AlreadyExists) => {
+                ErrorKind::AlreadyExists.into()
+            }
+            Error::Path(path::Error::NotEmpty) => {
+                ErrorKind::DirectoryNotEmpty.into()
+            }
+            Error::Path(path::Error::InsideItself) => {
+                ErrorKind::InvalidInput.into()
+            }
+            Error::File(e) => {
+                std::io::Error::new(ErrorKind::PermissionDenied, e)
+            }
+            e => std::io::Error::other(e),
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! # Purpose
+//! Reads and writes the File Allocation Table, which links the clusters of
+//! fragmented files and directories into chains.
+
+pub mod chain;
+
+use core::fmt::Display;
+
+use alloc::vec;
+
+pub use chain::Chain;
+
+use crate::{
+    block_device::BlockDevice,
+    fat_entry::{FatEntry, Link},
+    super_block::SuperBlock,
+};
+
+/// Ways a FAT can fail to describe a sound cluster chain.
+#[derive(Debug)]
+pub enum Error {
+    /// A cluster outside of the Cluster Heap, which spans clusters 2 through
+    /// ClusterCount + 1, was asked for or linked to.
+    OutOfRange { cluster: u32, cluster_count: u32 },
+    /// The chain starting at `first_cluster` loops back on itself.
+    Loop { first_cluster: u32 },
+    /// The entry of `cluster` neither links to another cluster nor ends the
+    /// chain `cluster` is part of.
+    Broken { cluster: u32, entry: FatEntry },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::OutOfRange {
+                cluster,
+                cluster_count,
+            } => write!(
+                f,
+                "Cluster {} is outside of the cluster heap, which spans \
+                clusters 2 through {}.",
+                cluster,
+                *cluster_count as u64 + 1
+            ),
+            Error::Loop { first_cluster } => write!(
+                f,
+                "The cluster chain starting at cluster {} loops.",
+                first_cluster
+            ),
+            Error::Broken { cluster, entry } => write!(
+                f,
+                "The cluster chain is broken at cluster {} by FAT entry \
+                {:#010x}.",
+                cluster, entry.0
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Where the FATs of a volume are and which of them is in use.
+#[derive(Debug, Clone, Copy)]
+pub struct Fat {
+    /// The byte offset of the First FAT.
+    offset: u64,
+    /// The length of each FAT in bytes.
+    length: u64,
+    number_of_fats: u8,
+    /// 0 when the First FAT is active, 1 when the Second FAT is.
+    active: u8,
+    cluster_count: u32,
+}
+
+impl Fat {
+    /// Locates the FATs a boot sector describes.
+    pub fn new(boot_sector: &SuperBlock) -> Self {
+        let bytes_per_sector = *boot_sector.bytes_per_sector() as u64;
+        let active = boot_sector.volume_flags().active_fat();
+        Self {
+            offset: boot_sector.fat_offset() as u64 * bytes_per_sector,
+            length: boot_sector.fat_length() as u64 * bytes_per_sector,
+            number_of_fats: boot_sector.number_of_fats(),
+            active: if boot_sector.number_of_fats() == 2 {
+                active as u8
+            } else {
+                0
+            },
+            cluster_count: boot_sector.cluster_count(),
+        }
+    }
+
+    /// The number of clusters in the Cluster Heap, which the FAT has an entry
+    /// for each of.
+    pub fn cluster_count(&self) -> u32 {
+        self.cluster_count
+    }
+
+    /// 0 when the First FAT is active, 1 when the Second FAT is.
+    pub fn active(&self) -> u8 {
+        self.active
+    }
+
+    /// Whether `cluster` is part of the Cluster Heap.
+    pub fn contains(&self, cluster: u32) -> bool {
+        (FatEntry::FIRST_CLUSTER..=self.cluster_count.saturating_add(1))
+            .contains(&cluster)
+    }
+
+    fn check_range(&self, cluster: u32) -> Result<(), Error> {
+        if self.contains(cluster) {
+            Ok(())
+        } else {
+            Err(Error::OutOfRange {
+                cluster,
+                cluster_count: self.cluster_count,
+            })
+        }
+    }
+
+    /// The byte offset of the entry at `index` of the FAT numbered `fat`.
+    fn entry_offset(&self, fat: u8, index: u32) -> u64 {
+        self.offset
+            + self.length * fat as u64
+            + index as u64 * FatEntry::SIZE as u64
+    }
+
+    /// Reads the entry of `cluster` from the active FAT.
+    pub fn entry(
+        &self,
+        disk: &mut impl BlockDevice,
+        cluster: u32,
+    ) -> Result<FatEntry, crate::Error> {
+        self.check_range(cluster)?;
+        let mut entry = [0u8; FatEntry::SIZE];
+        disk.read_at(self.entry_offset(self.active, cluster), &mut entry)?;
+        Ok(FatEntry(u32::from_le_bytes(entry)))
+    }
+
+    /// Reads the entry of `cluster` from the active FAT and interprets it.
+    pub fn link(
+        &self,
+        disk: &mut impl BlockDevice,
+        cluster: u32,
+    ) -> Result<Link, crate::Error> {
+        Ok(self.entry(disk, cluster)?.link(self.cluster_count))
+    }
+
+    /// Writes the entry of `cluster` to the active FAT. The inactive FAT of a
+    /// TexFAT volume is stale and left alone.
+    pub fn set_entry(
+        &self,
+        disk: &mut impl BlockDevice,
+        cluster: u32,
+        entry: impl Into<FatEntry>,
+    ) -> Result<(), crate::Error> {
+        self.check_range(cluster)?;
+        let entry = entry.into().0.to_le_bytes();
+        disk.write_at(self.entry_offset(self.active, cluster), &entry)
+    }
+
+    /// Links `clusters` into a chain in the order given, ending it after the
+    /// last one.
+    pub fn set_chain(
+        &self,
+        disk: &mut impl BlockDevice,
+        clusters: impl IntoIterator<Item = u32>,
+    ) -> Result<(), crate::Error> {
+        let mut clusters = clusters.into_iter().peekable();
+        while let Some(cluster) = clusters.next() {
+            let link = clusters
+                .peek()
+                .map_or(Link::EndOfChain, |&next| Link::Next(next));
+            self.set_entry(disk, cluster, link)?;
+        }
+        Ok(())
+    }
+
+    /// Follows the chain of clusters starting at `first_cluster`.
+    pub fn chain<'a, Disk: BlockDevice>(
+        &'a self,
+        disk: &'a mut Disk,
+        first_cluster: u32,
+    ) -> Chain<'a, Disk> {
+        Chain::new(self, disk, first_cluster)
+    }
+
+    /// Writes every FAT of a newly formatted volume: the two reserved entries
+    /// followed by a free entry for every cluster.
+    pub fn init(
+        &self,
+        disk: &mut impl BlockDevice,
+    ) -> Result<(), crate::Error> {
+        // large enough to write most FATs in a few transfers, small enough
+        // to not strain the memory of small targets.
+        const CHUNK: u64 = 64 * 1024;
+        for fat in 0..self.number_of_fats {
+            let start = self.entry_offset(fat, 0);
+            let mut chunk = vec![0u8; CHUNK.min(self.length) as usize];
+            chunk[..FatEntry::SIZE]
+                .copy_from_slice(&FatEntry::MEDIA_TYPE.0.to_le_bytes());
+            chunk[FatEntry::SIZE..FatEntry::SIZE * 2]
+                .copy_from_slice(&FatEntry::RESERVED.0.to_le_bytes());
+            let mut written = 0;
+            while written < self.length {
+                let len = CHUNK.min(self.length - written) as usize;
+                disk.write_at(start + written, &chunk[..len])?;
+                if written == 0 {
+                    chunk[..FatEntry::SIZE * 2].fill(0);
+                }
+                written += len as u64;
+            }
+        }
+        disk.flush()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        block_device::BlockDevice,
+        fat_entry::{FatEntry, Link},
+        format_options::FormatOptions,
+        shift::ShiftedSectors,
+        test_util::{format_with, DISK_SIZE},
+        Error,
+    };
+
+    #[test]
+    pub fn init_and_entries() {
+        // formatting has to overwrite whatever was on the disk before
+        let mut fs = format_with(
+            vec![0xAA; DISK_SIZE],
+            ShiftedSectors::from(0),
+            &FormatOptions::default(),
+        );
+        let fat = *fs.fat();
+        let disk = fs.boot_region_mut().disk_mut();
+
+        let mut entry = [0u8; FatEntry::SIZE];
+        for (index, expected) in
+            [(0, FatEntry::MEDIA_TYPE), (1, FatEntry::RESERVED)]
+        {
+            disk.read_at(fat.entry_offset(0, index), &mut entry)
+                .unwrap();
+            assert_eq!(FatEntry(u32::from_le_bytes(entry)), expected);
+        }
+        let cluster_count = fat.cluster_count();
+        for cluster in [cluster_count / 2, cluster_count + 1] {
+            assert_eq!(fat.link(disk, cluster).unwrap(), Link::Free);
+        }
+        assert!(matches!(
+            fat.entry(disk, 1),
+            Err(Error::Fat(super::Error::OutOfRange { cluster: 1, .. }))
+        ));
+        assert!(matches!(
+            fat.entry(disk, cluster_count + 2),
+            Err(Error::Fat(super::Error::OutOfRange { .. }))
+        ));
+
+        fat.set_entry(disk, 50, Link::Next(90)).unwrap();
+        fat.set_entry(disk, 90, Link::EndOfChain).unwrap();
+        assert_eq!(fat.link(disk, 50).unwrap(), Link::Next(90));
+        assert_eq!(fat.link(disk, 90).unwrap(), Link::EndOfChain);
+        assert_eq!(fat.link(disk, 60).unwrap(), Link::Free);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::{block_device::BlockDevice, fat_entry::Link};
+
+use super::{Error, Fat};
+
+/// Iterates over the clusters of a cluster chain, reading each link from the
+/// FAT as it goes.
+///
+/// The first error ends the iteration. Loops are detected with Brent's
+/// algorithm, so a corrupt FAT is reported within a few laps of the loop
+/// rather than after ClusterCount clusters.
+pub struct Chain<'a, Disk> {
+    fat: &'a Fat,
+    disk: &'a mut Disk,
+    first_cluster: u32,
+    next: Option<u32>,
+    /// The cluster every later cluster is compared against to find loops.
+    tortoise: u32,
+    /// The number of steps taken since `tortoise` last moved.
+    steps: u64,
+    /// How many steps are taken before `tortoise` moves again.
+    power: u64,
+}
+
+impl<'a, Disk: BlockDevice> Chain<'a, Disk> {
+    pub fn new(fat: &'a Fat, disk: &'a mut Disk, first_cluster: u32) -> Self {
+        Self {
+            fat,
+            disk,
+            first_cluster,
+            next: Some(first_cluster),
+            tortoise: first_cluster,
+            steps: 0,
+            power: 1,
+        }
+    }
+
+    /// Reads the link of `cluster`, returning the cluster after it, if any.
+    fn follow(&mut self, cluster: u32) -> Result<Option<u32>, crate::Error> {
+        let entry = self.fat.entry(self.disk, cluster)?;
+        let next = match entry.link(self.fat.cluster_count()) {
+            Link::Next(next) => next,
+            Link::EndOfChain => return Ok(None),
+            Link::Free | Link::Bad | Link::Invalid(_) => {
+                return Err(Error::Broken { cluster, entry }.into())
+            }
+        };
+        if next == self.tortoise {
+            return Err(Error::Loop {
+                first_cluster: self.first_cluster,
+            }
+            .into());
+        }
+        self.steps += 1;
+        if self.steps == self.power {
+            self.tortoise = next;
+            self.steps = 0;
+            self.power *= 2;
+        }
+        Ok(Some(next))
+    }
+}
+
+impl<Disk: BlockDevice> Iterator for Chain<'_, Disk> {
+    type Item = Result<u32, crate::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cluster = self.next.take()?;
+        if !self.fat.contains(cluster) {
+            return Some(Err(Error::OutOfRange {
+                cluster,
+                cluster_count: self.fat.cluster_count(),
+            }
+            .into()));
+        }
+        match self.follow(cluster) {
+            Ok(next) => {
+                self.next = next;
+                Some(Ok(cluster))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        fat::{self, Fat},
+        fat_entry::Link,
+        format_options::FormatOptions,
+        shift::ShiftedSectors,
+        test_util::{format_with, MemoryDisk as Disk},
+        Error, FileSystem,
+    };
+
+    fn formatted() -> (Fat, FileSystem<Disk>) {
+        let fs = format_with(
+            vec![0; 2usize.pow(22)],
+            ShiftedSectors::from(0),
+            &FormatOptions::default(),
+        );
+        (*fs.fat(), fs)
+    }
+
+    fn link(fat: &Fat, disk: &mut Disk, clusters: &[u32], last: Link) {
+        for pair in clusters.windows(2) {
+            fat.set_entry(disk, pair[0], Link::Next(pair[1])).unwrap();
+        }
+        fat.set_entry(disk, *clusters.last().unwrap(), last)
+            .unwrap();
+    }
+
+    #[test]
+    pub fn follows_chains() {
+        let (fat, mut fs) = formatted();
+        let disk = fs.boot_region_mut().disk_mut();
+
+        let clusters = [10, 11, 50, 3, 1000];
+        link(&fat, disk, &clusters, Link::EndOfChain);
+        let chain: Vec<u32> =
+            fat.chain(disk, 10).collect::<Result<_, _>>().unwrap();
+        assert_eq!(chain, clusters);
+
+        link(&fat, disk, &[20], Link::EndOfChain);
+        let chain: Vec<u32> =
+            fat.chain(disk, 20).collect::<Result<_, _>>().unwrap();
+        assert_eq!(chain, [20]);
+    }
+
+    #[test]
+    pub fn detects_corruption() {
+        let (fat, mut fs) = formatted();
+        let disk = fs.boot_region_mut().disk_mut();
+        let cluster_count = fat.cluster_count();
+
+        // loops of every length, entered after varying lead ins
+        for lead_in in 0..5u32 {
+            for loop_length in 1..40u32 {
+                let clusters: Vec<u32> =
+                    (100..100 + lead_in + loop_length).collect();
+                let loop_start = clusters[lead_in as usize];
+                link(&fat, disk, &clusters, Link::Next(loop_start));
+                let chain: Vec<_> = fat.chain(disk, 100).collect();
+                assert!(chain.len() <= 4 * (lead_in + loop_length) as usize);
+                assert!(matches!(
+                    chain.last(),
+                    Some(Err(Error::Fat(fat::Error::Loop {
+                        first_cluster: 100
+                    })))
+                ));
+            }
+        }
+
+        link(&fat, disk, &[5, 6, 7], Link::Bad);
+        let chain: Vec<_> = fat.chain(disk, 5).collect();
+        assert_eq!(chain.len(), 3);
+        assert!(matches!(
+            chain[2],
+            Err(Error::Fat(fat::Error::Broken { cluster: 7, .. }))
+        ));
+
+        // a link past the end of the cluster heap
+        link(&fat, disk, &[5, 6], Link::Next(cluster_count + 2));
+        assert!(matches!(
+            fat.chain(disk, 5).last(),
+            Some(Err(Error::Fat(fat::Error::Broken { cluster: 6, .. })))
+        ));
+
+        // an unlinked cluster in the middle of a chain
+        link(&fat, disk, &[5, 6], Link::Next(8));
+        fat.set_entry(disk, 8, Link::Free).unwrap();
+        assert!(matches!(
+            fat.chain(disk, 5).last(),
+            Some(Err(Error::Fat(fat::Error::Broken { cluster: 8, .. })))
+        ));
+
+        for first_cluster in [0, 1, cluster_count + 2] {
+            assert!(matches!(
+                fat.chain(disk, first_cluster).collect::<Vec<_>>()[..],
+                [Err(Error::Fat(fat::Error::OutOfRange { .. }))]
+            ));
+        }
+    }
+}
@@ -1 +1,95 @@
-pub struct FatEntry(u32);
+use serde::{Deserialize, Serialize};
+
+/// The raw value of one entry of a FAT.
+///
+/// Entries 0 and 1 are reserved, the rest describe the cluster of the Cluster
+/// Heap with the same index.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FatEntry(pub u32);
+
+/// What an entry describing a cluster of the Cluster Heap says about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// The entry is zero. The Allocation Bitmap, not the FAT, is what records
+    /// whether a cluster is free; a zero entry only ever appears on clusters
+    /// which are not part of any chain.
+    Free,
+    /// The cluster is followed by the given cluster.
+    Next(u32),
+    /// The cluster is bad, FFFFFFF7h.
+    Bad,
+    /// The cluster is the last of its chain, FFFFFFFFh.
+    EndOfChain,
+    /// Any value which is none of the above, such as a link past the end of
+    /// the Cluster Heap.
+    Invalid(u32),
+}
+
+impl FatEntry {
+    /// The number of bytes an entry takes up in a FAT.
+    pub const SIZE: usize = size_of::<u32>();
+    /// The first cluster of the Cluster Heap.
+    pub const FIRST_CLUSTER: u32 = 2;
+    /// The valid value of FatEntry[0], the media type: F8h in the first byte
+    /// and FFh in the rest.
+    pub const MEDIA_TYPE: Self = Self(0xFFFF_FFF8);
+    /// The valid value of FatEntry[1], which only exists due to historical
+    /// precedence.
+    pub const RESERVED: Self = Self(0xFFFF_FFFF);
+    pub const FREE: Self = Self(0);
+    pub const BAD: Self = Self(0xFFFF_FFF7);
+    pub const END_OF_CHAIN: Self = Self(0xFFFF_FFFF);
+
+    /// Interprets the entry of a cluster in a Cluster Heap of `cluster_count`
+    /// clusters.
+    pub fn link(&self, cluster_count: u32) -> Link {
+        match *self {
+            Self::FREE => Link::Free,
+            Self::BAD => Link::Bad,
+            Self::END_OF_CHAIN => Link::EndOfChain,
+            Self(next)
+                if (Self::FIRST_CLUSTER..=cluster_count.saturating_add(1))
+                    .contains(&next) =>
+            {
+                Link::Next(next)
+            }
+            Self(value) => Link::Invalid(value),
+        }
+    }
+}
+
+impl From<Link> for FatEntry {
+    fn from(value: Link) -> Self {
+        match value {
+            Link::Free => Self::FREE,
+            Link::Next(cluster) => Self(cluster),
+            Link::Bad => Self::BAD,
+            Link::EndOfChain => Self::END_OF_CHAIN,
+            Link::Invalid(value) => Self(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FatEntry, Link};
+
+    #[test]
+    pub fn link() {
+        let cluster_count = 100;
+        assert_eq!(FatEntry(0).link(cluster_count), Link::Free);
+        assert_eq!(FatEntry(1).link(cluster_count), Link::Invalid(1));
+        assert_eq!(FatEntry(2).link(cluster_count), Link::Next(2));
+        assert_eq!(FatEntry(101).link(cluster_count), Link::Next(101));
+        assert_eq!(FatEntry(102).link(cluster_count), Link::Invalid(102));
+        assert_eq!(FatEntry(0xFFFF_FFF7).link(cluster_count), Link::Bad);
+        assert_eq!(
+            FatEntry(0xFFFF_FFF8).link(cluster_count),
+            Link::Invalid(0xFFFF_FFF8)
+        );
+        assert_eq!(FatEntry(0xFFFF_FFFF).link(cluster_count), Link::EndOfChain);
+        for link in [Link::Free, Link::Next(7), Link::Bad, Link::EndOfChain] {
+            assert_eq!(FatEntry::from(link).link(cluster_count), link);
+        }
+    }
+}
@@ -0,0 +1,933 @@
+//! # Purpose
+//! Reads and writes the data of a file through its cluster chain, or straight
+//! from its clusters when they are contiguous.
+//!
+//! Bytes between ValidDataLength and DataLength have never been written, so
+//! they read as zeros without touching the volume.
+//!
+//! A file which grows is allocated one run of clusters and marked NoFatChain
+//! for as long as the clusters after its last one are free. Once they are
+//! not, its clusters are linked into a FAT chain and the flag is cleared.
+//!
+//! Clusters can also be allocated ahead of any writes with
+//! [`File::preallocate`], which moves DataLength but not ValidDataLength, so
+//! the space is claimed without zero-filling it.
+//!
+//! A file with the ReadOnly attribute is only opened for writing when
+//! [`OpenOptions::ignore_read_only`] says so. Writing to a file sets its
+//! Archive attribute.
+
+use core::fmt::Display;
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    allocation_bitmap,
+    block_device::BlockDevice,
+    dir_entry::FileAttributes,
+    directory::{self, Position},
+    entry_set::EntrySet,
+    error::Error as CrateError,
+    fat_entry::{FatEntry, Link},
+    stream::{self, Clusters, Stream},
+    FileSystem,
+};
+
+/// Ways a file can refuse to be written to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The file has the ReadOnly attribute, so it cannot be opened for
+    /// writing.
+    ReadOnly,
+    /// The file was opened for reading only.
+    NotWritable,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::ReadOnly => write!(f, "The file is read-only."),
+            Error::NotWritable => {
+                write!(f, "The file was not opened for writing.")
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// How [`FileSystem::open_with`] opens a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OpenOptions {
+    /// Whether the file can be written to. A file with the ReadOnly
+    /// attribute cannot, unless `ignore_read_only` is set as well.
+    pub write: bool,
+    /// Opens the file for writing even if it has the ReadOnly attribute.
+    pub ignore_read_only: bool,
+}
+
+impl OpenOptions {
+    /// Options which open a file for reading only.
+    pub fn read_only() -> Self {
+        Self::default()
+    }
+
+    /// Options which open a file for reading and writing.
+    pub fn read_write() -> Self {
+        Self {
+            write: true,
+            ..Self::default()
+        }
+    }
+}
+
+/// An open file of a [`FileSystem`].
+///
+/// With the `std` feature it implements [`std::io::Read`],
+/// [`std::io::Write`] and [`std::io::Seek`], otherwise [`File::read_at`] and
+/// [`File::write_at`] work at any offset.
+///
+/// Changes to the length and timestamps of the file are written to its entry
+/// set by [`File::sync`], which is also done when the file is dropped.
+pub struct File<'a, Disk: BlockDevice> {
+    fs: &'a mut FileSystem<Disk>,
+    /// The directory holding the entry set of the file.
+    directory: Stream,
+    /// Where the entry set of the file starts within `directory`.
+    location: Position,
+    entry_set: EntrySet,
+    clusters: Clusters,
+    /// The offset the next read or write starts at.
+    offset: u64,
+    /// Whether the entry set has changed since it was last written.
+    dirty: bool,
+    /// Whether the file was opened for writing.
+    writable: bool,
+}
+
+impl<'a, Disk: BlockDevice> File<'a, Disk> {
+    /// Opens the file described by `entry_set`, which is at `location` in
+    /// `directory`, for writing as well if `writable`. Whether the file is
+    /// ReadOnly is up to the caller to check.
+    pub(crate) fn new(
+        fs: &'a mut FileSystem<Disk>,
+        directory: Stream,
+        location: Position,
+        entry_set: EntrySet,
+        writable: bool,
+    ) -> Result<Self, CrateError> {
+        let stream = Stream::from(&entry_set.stream_extension);
+        let heap = fs.cluster_heap();
+        let fat = *fs.fat();
+        let clusters =
+            stream.locate(heap, &fat, fs.boot_region_mut().disk_mut())?;
+        if (clusters.len() as u64) < heap.clusters_for(stream.data_length) {
+            return Err(stream::Error::TooShort {
+                data_length: stream.data_length,
+                clusters: clusters.len(),
+            }
+            .into());
+        }
+        Ok(Self {
+            fs,
+            directory,
+            location,
+            entry_set,
+            clusters,
+            offset: 0,
+            dirty: false,
+            writable,
+        })
+    }
+
+    pub fn entry_set(&self) -> &EntrySet {
+        &self.entry_set
+    }
+
+    /// The directory holding the entry set of the file, and where the entry
+    /// set starts within it.
+    pub fn location(&self) -> (Stream, Position) {
+        (self.directory, self.location)
+    }
+
+    /// The length of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.entry_set.stream_extension.data_length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bytes from the start of the file which have been
+    /// written.
+    pub fn valid_data_length(&self) -> u64 {
+        self.entry_set
+            .stream_extension
+            .valid_data_length
+            .min(self.len())
+    }
+
+    pub fn attributes(&self) -> FileAttributes {
+        self.entry_set.file.file_attributes
+    }
+
+    /// Replaces the attributes of the file, which is allowed even if it was
+    /// opened for reading only. The Directory attribute of `attributes` is
+    /// ignored.
+    pub fn set_attributes(&mut self, mut attributes: FileAttributes) {
+        attributes.remove(FileAttributes::DIRECTORY);
+        self.entry_set.file.file_attributes = attributes;
+        self.dirty = true;
+    }
+
+    /// Whether the file was opened for writing.
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    /// The offset the next read or write through the `std::io` traits
+    /// starts at.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads from `offset` into `buf`, returning the number of bytes read,
+    /// which is only less than `buf.len()` at the end of the file.
+    pub fn read_at(
+        &mut self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<usize, CrateError> {
+        let len = self.len().saturating_sub(offset).min(buf.len() as u64);
+        let buf = &mut buf[..len as usize];
+        let valid = self.valid_data_length();
+        let heap = self.fs.cluster_heap();
+        let bytes_per_cluster = heap.bytes_per_cluster();
+        let mut done = 0;
+        while done < buf.len() {
+            let offset = offset + done as u64;
+            if offset >= valid {
+                buf[done..].fill(0);
+                done = buf.len();
+                break;
+            }
+            let index = (offset / bytes_per_cluster) as usize;
+            let within = offset % bytes_per_cluster;
+            let cluster = self
+                .clusters
+                .get(index)
+                .expect("the allocation was checked to hold DataLength");
+            // read as many clusters as follow each other at once
+            let run = self.clusters.run(index) as u64 * bytes_per_cluster;
+            let count = (run - within)
+                .min(valid - offset)
+                .min((buf.len() - done) as u64)
+                as usize;
+            self.fs.boot_region_mut().disk_mut().read_at(
+                heap.cluster_offset(cluster) + within,
+                &mut buf[done..done + count],
+            )?;
+            done += count;
+        }
+        Ok(done)
+    }
+
+    /// Writes `buf` at `offset`, allocating clusters as the file grows.
+    ///
+    /// Writing past ValidDataLength first fills the gap with zeros, as the
+    /// data there would otherwise be whatever the clusters held before.
+    pub fn write_at(
+        &mut self,
+        offset: u64,
+        buf: &[u8],
+    ) -> Result<usize, CrateError> {
+        self.check_writable()?;
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let heap = self.fs.cluster_heap();
+        let end = offset.saturating_add(buf.len() as u64);
+        self.grow(self.clusters_for(end)?, false)?;
+        let valid = self.valid_data_length();
+        if offset > valid {
+            let zeros =
+                vec![0; heap.bytes_per_cluster().min(offset - valid) as usize];
+            let mut at = valid;
+            while at < offset {
+                let count = (offset - at).min(zeros.len() as u64) as usize;
+                self.write_clusters(at, &zeros[..count])?;
+                at += count as u64;
+            }
+        }
+        self.write_clusters(offset, buf)?;
+
+        let stream_extension = &mut self.entry_set.stream_extension;
+        stream_extension.valid_data_length = valid.max(end);
+        stream_extension.data_length = stream_extension.data_length.max(end);
+        self.touch();
+        Ok(buf.len())
+    }
+
+    /// Truncates or extends the file to `len` bytes.
+    ///
+    /// Extending only allocates clusters, as the bytes added are past
+    /// ValidDataLength and so read as zeros without having been written.
+    /// Truncating writes the shorter entry set before freeing the clusters
+    /// past the new end.
+    pub fn set_len(&mut self, len: u64) -> Result<(), CrateError> {
+        self.check_writable()?;
+        if len >= self.len() {
+            return self.preallocate(len, false);
+        }
+        let keep = self.clusters_for(len)?;
+        let tail = match &mut self.clusters {
+            Clusters::Contiguous(range) => {
+                let tail = range.start + keep..range.end;
+                range.end = tail.start;
+                Clusters::Contiguous(tail)
+            }
+            Clusters::Chain(chain) => {
+                Clusters::Chain(chain.split_off(keep as usize))
+            }
+        };
+        let stream_extension = &mut self.entry_set.stream_extension;
+        if keep == 0 {
+            stream_extension.first_cluster = 0;
+            stream_extension
+                .general_secondary_flags
+                .set_no_fat_chain(false);
+            self.clusters = Clusters::Chain(Vec::new());
+        }
+        stream_extension.data_length = len;
+        stream_extension.valid_data_length =
+            stream_extension.valid_data_length.min(len);
+        self.touch();
+        self.sync()?;
+
+        if let Clusters::Chain(chain) = &self.clusters {
+            if let Some(&last) = chain.last() {
+                let fat = *self.fs.fat();
+                let disk = self.fs.boot_region_mut().disk_mut();
+                fat.set_entry(disk, last, Link::EndOfChain)?;
+            }
+        }
+        self.fs.free_clusters(&tail)
+    }
+
+    /// Allocates clusters for the file to be `len` bytes long without
+    /// writing to them, leaving ValidDataLength where it is. A file which is
+    /// at least `len` bytes long already is left alone.
+    ///
+    /// With `contiguous`, the clusters added have to directly follow the
+    /// last cluster of the file, or be a single run if the file has none, so
+    /// that an empty file ends up in one run without a FAT chain. If no such
+    /// run is free, nothing is allocated and
+    /// [`allocation_bitmap::Error::Fragmented`] is returned.
+    pub fn preallocate(
+        &mut self,
+        len: u64,
+        contiguous: bool,
+    ) -> Result<(), CrateError> {
+        self.check_writable()?;
+        if len <= self.len() {
+            return Ok(());
+        }
+        self.grow(self.clusters_for(len)?, contiguous)?;
+        self.entry_set.stream_extension.data_length = len;
+        self.touch();
+        Ok(())
+    }
+
+    fn check_writable(&self) -> Result<(), Error> {
+        if self.writable {
+            Ok(())
+        } else {
+            Err(Error::NotWritable)
+        }
+    }
+
+    /// Stamps the file as modified, and so also accessed, now, and marks it
+    /// to be archived. Directories grown through a `File` are only stamped.
+    fn touch(&mut self) {
+        let now = self.fs.timestamp();
+        let file = &mut self.entry_set.file;
+        file.set_modified(now);
+        file.set_accessed(now);
+        if !file.is_directory() {
+            file.file_attributes.insert(FileAttributes::ARCHIVE);
+        }
+        self.dirty = true;
+    }
+
+    /// The number of clusters needed to hold `len` bytes.
+    fn clusters_for(&self, len: u64) -> Result<u32, CrateError> {
+        let clusters = self.fs.cluster_heap().clusters_for(len);
+        u32::try_from(clusters).map_err(|_| {
+            allocation_bitmap::Error::Full {
+                requested: u32::MAX,
+                free: self.fs.free_cluster_count(),
+            }
+            .into()
+        })
+    }
+
+    /// Writes `buf` to the clusters backing `offset` onwards, which have to
+    /// be allocated already.
+    fn write_clusters(
+        &mut self,
+        offset: u64,
+        buf: &[u8],
+    ) -> Result<(), CrateError> {
+        let heap = self.fs.cluster_heap();
+        let bytes_per_cluster = heap.bytes_per_cluster();
+        let mut done = 0;
+        while done < buf.len() {
+            let offset = offset + done as u64;
+            let index = (offset / bytes_per_cluster) as usize;
+            let within = offset % bytes_per_cluster;
+            let cluster = self
+                .clusters
+                .get(index)
+                .expect("the allocation was grown to hold the write");
+            let run = self.clusters.run(index) as u64 * bytes_per_cluster;
+            let count = (run - within).min((buf.len() - done) as u64) as usize;
+            self.fs.boot_region_mut().disk_mut().write_at(
+                heap.cluster_offset(cluster) + within,
+                &buf[done..done + count],
+            )?;
+            done += count;
+        }
+        Ok(())
+    }
+
+    /// Makes the allocation of the file at least `count` clusters long.
+    /// With `contiguous`, the clusters added have to follow on from the last
+    /// one the file has, or form a single run if it has none.
+    fn grow(&mut self, count: u32, contiguous: bool) -> Result<(), CrateError> {
+        let allocated = self.clusters.len() as u32;
+        if count <= allocated {
+            return Ok(());
+        }
+        let needed = count - allocated;
+        let free = self.fs.free_cluster_count();
+        if needed > free {
+            return Err(allocation_bitmap::Error::Full {
+                requested: needed,
+                free,
+            }
+            .into());
+        }
+        let fragmented =
+            allocation_bitmap::Error::Fragmented { requested: needed };
+        let fat = *self.fs.fat();
+        let stream_extension = &mut self.entry_set.stream_extension;
+        match &mut self.clusters {
+            Clusters::Contiguous(range) if range.start < range.end => {
+                if self.fs.allocate_at(range.end, needed)? {
+                    range.end += needed;
+                } else if contiguous {
+                    return Err(fragmented.into());
+                } else {
+                    let mut chain: Vec<u32> = range.clone().collect();
+                    chain.extend(self.fs.allocate(range.end, needed)?);
+                    let disk = self.fs.boot_region_mut().disk_mut();
+                    fat.set_chain(disk, chain.iter().copied())?;
+                    stream_extension
+                        .general_secondary_flags
+                        .set_no_fat_chain(false);
+                    self.clusters = Clusters::Chain(chain);
+                }
+            }
+            Clusters::Chain(chain) if !chain.is_empty() => {
+                let last = chain[chain.len() - 1];
+                let new = if !contiguous {
+                    self.fs.allocate(last + 1, needed)?
+                } else if self.fs.allocate_at(last + 1, needed)? {
+                    (last + 1..last + 1 + needed).collect()
+                } else {
+                    return Err(fragmented.into());
+                };
+                let disk = self.fs.boot_region_mut().disk_mut();
+                fat.set_chain(
+                    disk,
+                    [last].into_iter().chain(new.iter().copied()),
+                )?;
+                chain.extend(new);
+            }
+            _ => {
+                let new = if contiguous {
+                    let first = self
+                        .fs
+                        .allocation_bitmap()
+                        .find_free(FatEntry::FIRST_CLUSTER, needed)
+                        .ok_or(fragmented)?;
+                    self.fs.allocate_at(first, needed)?;
+                    Clusters::Contiguous(first..first + needed)
+                } else {
+                    let new =
+                        self.fs.allocate(FatEntry::FIRST_CLUSTER, needed)?;
+                    let first = new[0];
+                    if new
+                        .iter()
+                        .zip(first..)
+                        .all(|(cluster, expected)| *cluster == expected)
+                    {
+                        Clusters::Contiguous(first..first + needed)
+                    } else {
+                        let disk = self.fs.boot_region_mut().disk_mut();
+                        fat.set_chain(disk, new.iter().copied())?;
+                        Clusters::Chain(new)
+                    }
+                };
+                stream_extension.first_cluster =
+                    new.get(0).expect("at least one cluster was allocated");
+                stream_extension
+                    .general_secondary_flags
+                    .set_no_fat_chain(matches!(new, Clusters::Contiguous(_)));
+                self.clusters = new;
+            }
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Writes the entry set of the file back to its directory, with its
+    /// SetChecksum updated, if it has changed.
+    pub fn sync(&mut self) -> Result<(), CrateError> {
+        if self.dirty {
+            let bytes = self.entry_set.to_bytes()?;
+            let heap = self.fs.cluster_heap();
+            let fat = *self.fs.fat();
+            directory::write_entries(
+                heap,
+                &fat,
+                self.fs.boot_region_mut().disk_mut(),
+                self.directory,
+                self.location,
+                &bytes,
+            )?;
+            self.dirty = false;
+        }
+        self.fs.boot_region_mut().disk_mut().flush()
+    }
+}
+
+impl<Disk: BlockDevice> Drop for File<'_, Disk> {
+    fn drop(&mut self) {
+        if self.dirty {
+            // errors can only be seen by syncing before the file is dropped
+            let _ = self.sync();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Disk: BlockDevice> std::io::Write for File<'_, Disk> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.write_at(self.offset, buf)?;
+        self.offset += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(self.sync()?)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Disk: BlockDevice> std::io::Read for File<'_, Disk> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.read_at(self.offset, buf)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Disk: BlockDevice> std::io::Seek for File<'_, Disk> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let offset = match pos {
+            std::io::SeekFrom::Start(offset) => Some(offset),
+            std::io::SeekFrom::End(delta) => {
+                self.len().checked_add_signed(delta)
+            }
+            std::io::SeekFrom::Current(delta) => {
+                self.offset.checked_add_signed(delta)
+            }
+        };
+        self.offset = offset.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "seek to a negative or overflowing offset",
+            )
+        })?;
+        Ok(self.offset)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use crate::{
+        allocation_bitmap,
+        block_device::BlockDevice,
+        dir_entry::FileAttributes,
+        directory::Position,
+        entry_set::EntrySet,
+        path,
+        test_util::{format, utf16},
+        time::{DateTime, FixedTime, Timestamp},
+        Error, FileSystem,
+    };
+
+    use super::OpenOptions;
+
+    #[test]
+    pub fn read_and_seek() {
+        let mut fs = format();
+        let root = fs.root_directory().first_cluster;
+        let heap = fs.cluster_heap();
+        let fat = *fs.fat();
+        let cluster_size = heap.bytes_per_cluster() as usize;
+
+        // a fragmented file whose last 2 clusters were allocated but never
+        // written, and a contiguous one without a FAT chain
+        let fragmented = [root + 1, root + 3, root + 2, root + 4];
+        let data: Vec<u8> =
+            (0..4 * cluster_size).map(|i| (i % 251) as u8 + 1).collect();
+        let mut chained = EntrySet::new(
+            &utf16("chained"),
+            FileAttributes::empty(),
+            fs.upcase_table(),
+        )
+        .unwrap();
+        chained.stream_extension.first_cluster = fragmented[0];
+        chained.stream_extension.valid_data_length =
+            2 * cluster_size as u64 + 10;
+        chained.stream_extension.data_length = 4 * cluster_size as u64 - 100;
+        let contiguous_start = root + 10;
+        let mut contiguous = EntrySet::new(
+            &utf16("contiguous"),
+            FileAttributes::empty(),
+            fs.upcase_table(),
+        )
+        .unwrap();
+        contiguous.stream_extension.first_cluster = contiguous_start;
+        contiguous.stream_extension.valid_data_length = data.len() as u64;
+        contiguous.stream_extension.data_length = data.len() as u64;
+        contiguous
+            .stream_extension
+            .general_secondary_flags
+            .set_no_fat_chain(true);
+        let mut dir = EntrySet::new(
+            &utf16("dir"),
+            FileAttributes::DIRECTORY,
+            fs.upcase_table(),
+        )
+        .unwrap();
+
+        let mut entries = chained.to_bytes().unwrap();
+        entries.extend(contiguous.to_bytes().unwrap());
+        entries.extend(dir.to_bytes().unwrap());
+        let disk = fs.boot_region_mut().disk_mut();
+        let at = Position {
+            cluster: root,
+            entry: 2,
+        };
+        disk.write_at(at.offset(heap), &entries).unwrap();
+        fat.set_chain(&mut *disk, fragmented).unwrap();
+        for (cluster, chunk) in fragmented.iter().zip(data.chunks(cluster_size))
+        {
+            disk.write_at(heap.cluster_offset(*cluster), chunk).unwrap();
+        }
+        disk.write_at(heap.cluster_offset(contiguous_start), &data)
+            .unwrap();
+
+        let mut file = fs.open("/Chained").unwrap();
+        assert_eq!(file.len(), 4 * cluster_size as u64 - 100);
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        let valid = 2 * cluster_size + 10;
+        assert_eq!(read.len(), 4 * cluster_size - 100);
+        assert_eq!(read[..valid], data[..valid]);
+        assert!(read[valid..].iter().all(|byte| *byte == 0));
+
+        // across the jump from the first to the second cluster
+        let mut buf = [0; 20];
+        file.seek(SeekFrom::Start(cluster_size as u64 - 10))
+            .unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[cluster_size - 10..cluster_size + 10]);
+        assert_eq!(file.seek(SeekFrom::End(-5)).unwrap(), file.len() - 5);
+        assert_eq!(file.read(&mut buf).unwrap(), 5);
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+        assert!(file
+            .seek(SeekFrom::Current(-(file.len() as i64) - 1))
+            .is_err());
+
+        drop(file);
+
+        let mut file = fs.open("contiguous").unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+        file.seek(SeekFrom::Start(3 * cluster_size as u64 - 1))
+            .unwrap();
+        file.read_exact(&mut buf[..2]).unwrap();
+        assert_eq!(buf[..2], data[3 * cluster_size - 1..3 * cluster_size + 1]);
+
+        drop(file);
+
+        assert!(matches!(
+            fs.open("/dir"),
+            Err(Error::Path(path::Error::IsADirectory))
+        ));
+        assert!(matches!(
+            fs.open("/"),
+            Err(Error::Path(path::Error::IsADirectory))
+        ));
+        let error: std::io::Error = fs.open("/missing").err().unwrap().into();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    pub fn write_and_grow() {
+        let mut fs = format();
+        let now = DateTime {
+            year: 2024,
+            month: 6,
+            day: 30,
+            hour: 12,
+            minute: 34,
+            second: 57,
+            millisecond: 890,
+        };
+        fs.set_time_source(FixedTime(now));
+        let root = fs.root_directory().first_cluster;
+        let heap = fs.cluster_heap();
+        let cluster_size = heap.bytes_per_cluster() as usize;
+        let mut empty = EntrySet::new(
+            &utf16("new.bin"),
+            FileAttributes::empty(),
+            fs.upcase_table(),
+        )
+        .unwrap();
+        let at = Position {
+            cluster: root,
+            entry: 2,
+        };
+        let bytes = empty.to_bytes().unwrap();
+        let disk = fs.boot_region_mut().disk_mut();
+        disk.write_at(at.offset(heap), &bytes).unwrap();
+        let free = fs.free_cluster_count();
+
+        let first: Vec<u8> =
+            (0..cluster_size * 3 / 2).map(|i| (i % 253) as u8).collect();
+        let mut file =
+            fs.open_with("new.bin", OpenOptions::read_write()).unwrap();
+        file.write_all(&first).unwrap();
+        file.flush().unwrap();
+        drop(file);
+        let set = fs.lookup("new.bin").unwrap().entry_set().unwrap().clone();
+        let stream_extension = set.stream_extension;
+        assert!(stream_extension.general_secondary_flags.no_fat_chain());
+        assert_eq!(stream_extension.data_length, first.len() as u64);
+        assert_eq!(stream_extension.valid_data_length, first.len() as u64);
+        assert_eq!(set.file.modified().unwrap(), Timestamp::utc(now).unwrap());
+        assert_eq!(set.file.last_modified_10ms_increment, 189);
+        assert_eq!(fs.free_cluster_count(), free - 2);
+
+        // the cluster after the file is taken, so growing it needs a chain
+        let start = stream_extension.first_cluster;
+        assert!(fs.allocate_at(start + 2, 1).unwrap());
+        let second = vec![0x5A; cluster_size];
+        let mut file =
+            fs.open_with("new.bin", OpenOptions::read_write()).unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(&second).unwrap();
+        drop(file);
+        let set = fs.lookup("new.bin").unwrap().entry_set().unwrap().clone();
+        assert!(!set.stream_extension.general_secondary_flags.no_fat_chain());
+        let chain: Vec<u32> =
+            fs.cluster_chain(start).collect::<Result<_, _>>().unwrap();
+        assert_eq!(chain, [start, start + 1, start + 3]);
+
+        // writing past the end leaves zeros in between
+        let mut file =
+            fs.open_with("new.bin", OpenOptions::read_write()).unwrap();
+        let end = file.len();
+        file.write_at(end + 100, b"end").unwrap();
+        let mut read = Vec::new();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_to_end(&mut read).unwrap();
+        drop(file);
+        let mut expected = first.clone();
+        expected.extend(&second);
+        expected.extend([0; 100]);
+        expected.extend(b"end");
+        assert_eq!(read, expected);
+        let mut file = fs.open("new.bin").unwrap();
+        let mut reread = Vec::new();
+        file.read_to_end(&mut reread).unwrap();
+        assert_eq!(reread, expected);
+    }
+
+    #[test]
+    pub fn set_len_and_preallocate() {
+        let mut fs = format();
+        let cluster_size = fs.cluster_heap().bytes_per_cluster();
+        let free = fs.free_cluster_count();
+
+        let mut file = fs.create_file("video.bin").unwrap();
+        file.preallocate(10 * cluster_size, true).unwrap();
+        file.write_all(b"frame").unwrap();
+        file.preallocate(cluster_size, true).unwrap();
+        assert_eq!(file.len(), 10 * cluster_size);
+        assert_eq!(file.valid_data_length(), 5);
+        drop(file);
+        let set = fs.lookup("video.bin").unwrap().entry_set().unwrap().clone();
+        let stream_extension = set.stream_extension;
+        assert!(stream_extension.general_secondary_flags.no_fat_chain());
+        assert_eq!(stream_extension.data_length, 10 * cluster_size);
+        assert_eq!(stream_extension.valid_data_length, 5);
+        assert_eq!(fs.free_cluster_count(), free - 10);
+
+        // a contiguous allocation cannot skip over a cluster in use
+        let first = stream_extension.first_cluster;
+        assert!(fs.allocate_at(first + 10, 1).unwrap());
+        let mut file = fs
+            .open_with("video.bin", OpenOptions::read_write())
+            .unwrap();
+        assert!(matches!(
+            file.preallocate(11 * cluster_size, true),
+            Err(Error::AllocationBitmap(
+                allocation_bitmap::Error::Fragmented { requested: 1 }
+            ))
+        ));
+        assert!(matches!(
+            file.preallocate(u32::MAX as u64 * cluster_size, false),
+            Err(Error::AllocationBitmap(
+                allocation_bitmap::Error::Full { .. }
+            ))
+        ));
+        assert_eq!(file.len(), 10 * cluster_size);
+        file.preallocate(11 * cluster_size, false).unwrap();
+        drop(file);
+        assert_eq!(fs.free_cluster_count(), free - 12);
+        let chain: Vec<u32> =
+            fs.cluster_chain(first).collect::<Result<_, _>>().unwrap();
+        assert_eq!(chain.len(), 11);
+        assert_eq!(chain[10], first + 11);
+
+        let mut file = fs
+            .open_with("video.bin", OpenOptions::read_write())
+            .unwrap();
+        file.set_len(cluster_size + 2).unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        drop(file);
+        let mut expected = b"frame".to_vec();
+        expected.resize(cluster_size as usize + 2, 0);
+        assert_eq!(read, expected);
+        assert_eq!(fs.free_cluster_count(), free - 3);
+        let chain: Vec<u32> =
+            fs.cluster_chain(first).collect::<Result<_, _>>().unwrap();
+        assert_eq!(chain, [first, first + 1]);
+
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        let mut file = fs
+            .open_with("video.bin", OpenOptions::read_write())
+            .unwrap();
+        assert_eq!(file.len(), cluster_size + 2);
+        assert_eq!(file.valid_data_length(), 5);
+        file.set_len(3).unwrap();
+        file.set_len(0).unwrap();
+        drop(file);
+        let set = fs.lookup("video.bin").unwrap().entry_set().unwrap().clone();
+        assert_eq!(set.stream_extension.first_cluster, 0);
+        assert_eq!(set.stream_extension.valid_data_length, 0);
+        assert_eq!(fs.free_cluster_count(), free - 1);
+
+        // growing through set_len reads as zeros
+        let mut file = fs
+            .open_with("video.bin", OpenOptions::read_write())
+            .unwrap();
+        file.set_len(100).unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, [0; 100]);
+    }
+
+    #[test]
+    pub fn attributes() {
+        let mut fs = format();
+        fs.create_file("file").unwrap();
+        fs.create_dir("dir").unwrap();
+        assert_eq!(
+            fs.lookup("file").unwrap().attributes(),
+            FileAttributes::ARCHIVE
+        );
+        assert_eq!(
+            fs.lookup("/").unwrap().attributes(),
+            FileAttributes::DIRECTORY
+        );
+
+        // reserved bits are kept, while the Directory bit cannot change
+        let kept = FileAttributes::from_bits(0x8000)
+            | FileAttributes::READ_ONLY
+            | FileAttributes::HIDDEN
+            | FileAttributes::SYSTEM;
+        fs.set_attributes("file", kept | FileAttributes::DIRECTORY)
+            .unwrap();
+        fs.set_attributes("dir", FileAttributes::HIDDEN).unwrap();
+        assert_eq!(fs.lookup("file").unwrap().attributes(), kept);
+        assert_eq!(
+            fs.lookup("dir").unwrap().attributes(),
+            FileAttributes::HIDDEN | FileAttributes::DIRECTORY
+        );
+
+        assert!(matches!(
+            fs.open_with("file", OpenOptions::read_write()),
+            Err(Error::File(super::Error::ReadOnly))
+        ));
+        let mut file = fs.open("file").unwrap();
+        assert!(!file.is_writable());
+        assert!(matches!(
+            file.write_at(0, b"data"),
+            Err(Error::File(super::Error::NotWritable))
+        ));
+        assert!(matches!(
+            file.set_len(10),
+            Err(Error::File(super::Error::NotWritable))
+        ));
+        let error = file.write(b"data").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+        drop(file);
+
+        let mut file = fs
+            .open_with(
+                "file",
+                OpenOptions {
+                    write: true,
+                    ignore_read_only: true,
+                },
+            )
+            .unwrap();
+        file.write_all(b"data").unwrap();
+        assert_eq!(file.attributes(), kept | FileAttributes::ARCHIVE);
+        file.set_attributes(FileAttributes::from_bits(0x8000));
+        drop(file);
+
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(
+            fs.lookup("file").unwrap().attributes(),
+            FileAttributes::from_bits(0x8000)
+        );
+        fs.open_with("file", OpenOptions::read_write())
+            .unwrap()
+            .write_all(b"more")
+            .unwrap();
+    }
+}
@@ -1,16 +1,534 @@
+use alloc::{boxed::Box, vec, vec::Vec};
+use uguid::Guid;
+
 use crate::{
+    allocation_bitmap::AllocationBitmap,
+    block_device::BlockDevice,
+    boot_region::BootRegion,
+    cluster_heap::ClusterHeap,
+    dir_entry::{
+        FileAttributes, UpcaseTableEntry, VolumeGuidEntry, VolumeLabelEntry,
+    },
+    directory::{Entries, Entry},
+    entry_set::{name_hash, validate_name},
+    error::Error,
+    fat::{Chain, Fat},
+    fat_entry::FatEntry,
+    file::{self, File, OpenOptions},
+    format_options::FormatOptions,
+    handle::Handle,
     oem::Oem,
-    super_block::{boot_code::BootCode, SuperBlock},
+    path::{self, Component},
+    root_directory::RootEntries,
+    shift::{BytesPerSector, SectorsPerCluster},
+    stream::Stream,
+    super_block::{
+        boot_code::BootCode, extended_boot_code::ExtendedBootCode, BoundError,
+    },
+    time::{DateTime, TimeSource, Timestamp},
+    upcase_table::{table_checksum, UpcaseTable},
 };
 
-pub struct FileSystem {
-    // #[sectors(0)]
-    main_boot_sector: SuperBlock,
-    // #[sectors(0)]
-    backup_boot_sector: SuperBlock,
-    // #[sectors(0)]
-    oem_parameters: Oem,
-    reserved: (),
+mod label;
+mod tree;
+
+pub struct FileSystem<Disk> {
+    // #[sectors(0..24)]
+    boot_region: BootRegion<Disk>,
+    fat: Fat,
+    cluster_heap: ClusterHeap,
+    allocation_bitmap: AllocationBitmap,
+    upcase_table: UpcaseTable,
+    /// The volume label, empty when the volume has none.
+    volume_label: Vec<u16>,
+    volume_guid: Option<Guid>,
+    /// Where the timestamps of files come from.
+    time_source: Box<dyn TimeSource>,
+}
+
+impl<Disk: BlockDevice> FileSystem<Disk> {
+    /// Formats `disk` as an empty exFAT volume of `volume_length` bytes and
+    /// mounts it.
+    ///
+    /// The Allocation Bitmap, one per FAT, starts at cluster 2 and is
+    /// followed by the Up-case Table and then the root directory. The
+    /// mounted volume keeps taking timestamps from the time source of
+    /// `options`.
+    pub fn format(
+        bytes_per_sector: BytesPerSector,
+        sectors_per_cluster: SectorsPerCluster,
+        volume_length: u64,
+        options: &FormatOptions<impl TimeSource + Clone + 'static>,
+        disk: Disk,
+    ) -> Result<Self, Error> {
+        let volume_label: Vec<u16> = options
+            .volume_label
+            .as_deref()
+            .unwrap_or_default()
+            .encode_utf16()
+            .collect();
+        VolumeLabelEntry::validate(&volume_label)?;
+        let no_code = ExtendedBootCode::new(&[], bytes_per_sector)?;
+        let mut boot_region = BootRegion::format(
+            bytes_per_sector,
+            sectors_per_cluster,
+            BootCode::default(),
+            core::array::from_fn(|_| no_code.clone()),
+            volume_length,
+            Oem::new(),
+            options,
+            disk,
+        )?;
+        let boot_sector = boot_region.boot_sector();
+        let fat = Fat::new(boot_sector);
+        let cluster_heap = ClusterHeap::new(boot_sector);
+        let number_of_fats = boot_sector.number_of_fats() as u32;
+        let disk = boot_region.disk_mut();
+        fat.init(disk)?;
+
+        let cluster_count = cluster_heap.cluster_count();
+        let bitmap_clusters = cluster_heap
+            .clusters_for(AllocationBitmap::length(cluster_count))
+            as u32;
+        let upcase_table = UpcaseTable::generate();
+        let compressed_table = upcase_table.compress();
+        let upcase_first_cluster =
+            FatEntry::FIRST_CLUSTER + bitmap_clusters * number_of_fats;
+        // the root directory goes in the first cluster after those the
+        // Allocation Bitmaps and Up-case Table consume
+        let root = upcase_first_cluster
+            + cluster_heap.clusters_for(compressed_table.len() as u64) as u32;
+        if !cluster_heap.contains(root) {
+            return Err(BoundError::too_small(
+                "cluster_count",
+                cluster_count,
+                root - 1,
+            )
+            .into());
+        }
+
+        let mut allocation_bitmaps = Vec::new();
+        let mut allocation_bitmap = None;
+        for identifier in 0..number_of_fats {
+            let first_cluster =
+                FatEntry::FIRST_CLUSTER + bitmap_clusters * identifier;
+            let mut bitmap = AllocationBitmap::new(
+                cluster_heap,
+                first_cluster,
+                bitmap_clusters,
+            );
+            bitmap.mark(
+                FatEntry::FIRST_CLUSTER,
+                root - FatEntry::FIRST_CLUSTER + 1,
+                true,
+            )?;
+            bitmap.write(disk)?;
+            fat.set_chain(disk, bitmap.clusters().iter().copied())?;
+            allocation_bitmaps.push(bitmap.entry(identifier as u8));
+            if identifier == fat.active() as u32 {
+                allocation_bitmap = Some(bitmap);
+            }
+        }
+
+        disk.write_at(
+            cluster_heap.cluster_offset(upcase_first_cluster),
+            &compressed_table,
+        )?;
+        fat.set_chain(disk, upcase_first_cluster..root)?;
+
+        let root_entries = RootEntries {
+            allocation_bitmaps,
+            upcase_table: UpcaseTableEntry::new(
+                table_checksum(&compressed_table),
+                upcase_first_cluster,
+                compressed_table.len() as u64,
+            ),
+            volume_label: (!volume_label.is_empty())
+                .then(|| VolumeLabelEntry::new(&volume_label)),
+            volume_guid: options
+                .volume_guid
+                .map(VolumeGuidEntry::new)
+                .transpose()?,
+        };
+        root_entries.write(cluster_heap, &fat, disk, root)?;
+        boot_region.set_first_cluster_of_root_directory(root)?;
+
+        Ok(Self {
+            boot_region,
+            fat,
+            cluster_heap,
+            allocation_bitmap: allocation_bitmap
+                .expect("a volume has at least one FAT"),
+            upcase_table,
+            volume_label,
+            volume_guid: options.volume_guid,
+            time_source: Box::new(options.time_source.clone()),
+        })
+    }
+
+    /// Mounts an already formatted exFAT volume.
+    pub fn mount(disk: Disk) -> Result<Self, Error> {
+        let mut boot_region = BootRegion::open(disk)?;
+        let boot_sector = boot_region.boot_sector();
+        let fat = Fat::new(boot_sector);
+        let cluster_heap = ClusterHeap::new(boot_sector);
+        let root = boot_sector.first_cluster_of_root_directory();
+        let disk = boot_region.disk_mut();
+        let entries = RootEntries::read(cluster_heap, &fat, disk, root)?;
+        let allocation_bitmap = AllocationBitmap::load(
+            cluster_heap,
+            &fat,
+            disk,
+            entries.allocation_bitmap(&fat)?,
+        )?;
+        let upcase_table =
+            UpcaseTable::load(cluster_heap, &fat, disk, &entries.upcase_table)?;
+        Ok(Self {
+            boot_region,
+            fat,
+            cluster_heap,
+            allocation_bitmap,
+            upcase_table,
+            volume_label: entries
+                .volume_label
+                .map(|entry| entry.label().to_vec())
+                .unwrap_or_default(),
+            volume_guid: entries.volume_guid.map(|entry| entry.volume_guid()),
+            time_source: default_time_source(),
+        })
+    }
+
+    pub fn boot_region(&self) -> &BootRegion<Disk> {
+        &self.boot_region
+    }
+
+    pub fn boot_region_mut(&mut self) -> &mut BootRegion<Disk> {
+        &mut self.boot_region
+    }
+
+    pub fn fat(&self) -> &Fat {
+        &self.fat
+    }
+
+    pub fn cluster_heap(&self) -> ClusterHeap {
+        self.cluster_heap
+    }
+
+    pub fn allocation_bitmap(&self) -> &AllocationBitmap {
+        &self.allocation_bitmap
+    }
+
+    pub fn upcase_table(&self) -> &UpcaseTable {
+        &self.upcase_table
+    }
+
+    /// The GUID of the Volume GUID directory entry, if the volume has one.
+    pub fn volume_guid(&self) -> Option<Guid> {
+        self.volume_guid
+    }
+
+    /// Takes the timestamps of files from `time_source` from now on. Files
+    /// are stamped with the system clock when the `std` feature is enabled
+    /// and with 1980-01-01T00:00:00Z otherwise.
+    pub fn set_time_source(&mut self, time_source: impl TimeSource + 'static) {
+        self.time_source = Box::new(time_source);
+    }
+
+    /// The current date and time according to the time source.
+    pub fn now(&self) -> DateTime {
+        self.time_source.now()
+    }
+
+    /// The current date and time as a [`Timestamp`] in UTC, clamped to the
+    /// range a timestamp can hold.
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::saturating_utc(self.now())
+    }
+
+    /// The number of clusters not in use.
+    pub fn free_cluster_count(&self) -> u32 {
+        self.allocation_bitmap.free_count()
+    }
+
+    /// Allocates `count` clusters, preferring those at or after `hint`, see
+    /// [`AllocationBitmap::allocate`].
+    pub(crate) fn allocate(
+        &mut self,
+        hint: u32,
+        count: u32,
+    ) -> Result<Vec<u32>, Error> {
+        self.allocation_bitmap.allocate(
+            self.boot_region.disk_mut(),
+            hint,
+            count,
+        )
+    }
+
+    /// Allocates the `count` clusters starting at `first_cluster` if all of
+    /// them are free, returning whether they were.
+    pub(crate) fn allocate_at(
+        &mut self,
+        first_cluster: u32,
+        count: u32,
+    ) -> Result<bool, Error> {
+        if !self.allocation_bitmap.is_free(first_cluster, count) {
+            return Ok(false);
+        }
+        self.allocation_bitmap.set(
+            self.boot_region.disk_mut(),
+            first_cluster,
+            count,
+            true,
+        )?;
+        Ok(true)
+    }
+
+    /// Follows the chain of clusters starting at `first_cluster`.
+    pub fn cluster_chain(&mut self, first_cluster: u32) -> Chain<'_, Disk> {
+        self.fat.chain(self.boot_region.disk_mut(), first_cluster)
+    }
+
+    /// The allocation of the root directory.
+    pub fn root_directory(&self) -> Stream {
+        Stream::root(
+            self.boot_region
+                .boot_sector()
+                .first_cluster_of_root_directory(),
+        )
+    }
+
+    /// Lists the entries of the directory allocated at `directory`.
+    pub fn entries(
+        &mut self,
+        directory: Stream,
+    ) -> Result<Entries<'_, Disk>, Error> {
+        Entries::new(
+            self.cluster_heap,
+            &self.fat,
+            self.boot_region.disk_mut(),
+            directory,
+        )
+    }
+
+    /// Resolves `path`, such as `/a/b/c.txt`, one component at a time from
+    /// the root directory. Names are compared without regard to case.
+    pub fn lookup(&mut self, path: &str) -> Result<Handle, Error> {
+        let mut handles = self.resolve(path::components(path))?;
+        Ok(handles.pop().expect("the root is never popped"))
+    }
+
+    /// Resolves `components` one at a time from the root directory,
+    /// returning the root directory, every directory passed through and what
+    /// the last component leads to.
+    pub(crate) fn resolve(
+        &mut self,
+        components: impl IntoIterator<Item = Component>,
+    ) -> Result<Vec<Handle>, Error> {
+        let mut handles = vec![Handle::Root(self.root_directory())];
+        for component in components {
+            let name = match component {
+                Component::Parent => {
+                    if handles.len() > 1 {
+                        handles.pop();
+                    }
+                    continue;
+                }
+                Component::Name(name) => name,
+            };
+            let directory = handles.last().expect("the root is never popped");
+            if !directory.is_dir() {
+                return Err(path::Error::NotADirectory.into());
+            }
+            let found = self
+                .find(directory.stream(), &name)?
+                .ok_or(path::Error::NotFound)?;
+            handles.push(found);
+        }
+        Ok(handles)
+    }
+
+    /// Opens the file at `path` for reading.
+    pub fn open(&mut self, path: &str) -> Result<File<'_, Disk>, Error> {
+        self.open_with(path, OpenOptions::read_only())
+    }
+
+    /// Opens the file at `path` for reading, and for writing as well if
+    /// `options` say so and the file is not ReadOnly.
+    pub fn open_with(
+        &mut self,
+        path: &str,
+        options: OpenOptions,
+    ) -> Result<File<'_, Disk>, Error> {
+        match self.lookup(path)? {
+            Handle::Entry {
+                directory,
+                position,
+                entry_set,
+            } if !entry_set.file.is_directory() => {
+                let read_only = entry_set
+                    .file
+                    .file_attributes
+                    .contains(FileAttributes::READ_ONLY);
+                if options.write && read_only && !options.ignore_read_only {
+                    return Err(file::Error::ReadOnly.into());
+                }
+                File::new(self, directory, position, entry_set, options.write)
+            }
+            _ => Err(path::Error::IsADirectory.into()),
+        }
+    }
+
+    /// Finds the file or directory named `name` in `directory`.
+    ///
+    /// The NameHash of every entry set is compared before its name, and
+    /// entry sets which are malformed are passed over.
+    pub fn find(
+        &mut self,
+        directory: Stream,
+        name: &[u16],
+    ) -> Result<Option<Handle>, Error> {
+        validate_name(name)?;
+        let hash = name_hash(name, &self.upcase_table);
+        let entries = Entries::new(
+            self.cluster_heap,
+            &self.fat,
+            self.boot_region.disk_mut(),
+            directory,
+        )?;
+        for entry in entries {
+            let (position, entry_set) = match entry {
+                Ok((position, Entry::File(entry_set))) => (position, entry_set),
+                Ok(_) => continue,
+                Err(e) if e.is_device_error() => return Err(e),
+                Err(_) => continue,
+            };
+            if entry_set.stream_extension.name_hash == hash
+                && self.upcase_table.eq_ignore_case(entry_set.name(), name)
+            {
+                return Ok(Some(Handle::Entry {
+                    directory,
+                    position,
+                    entry_set,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn unmount(self) -> Disk {
+        self.boot_region.into_disk()
+    }
 }
 
-impl FileSystem {}
+#[cfg(feature = "std")]
+fn default_time_source() -> Box<dyn TimeSource> {
+    Box::new(crate::time::SystemClock)
+}
+
+#[cfg(not(feature = "std"))]
+fn default_time_source() -> Box<dyn TimeSource> {
+    Box::new(crate::time::FixedTime::default())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        block_device::BlockDevice,
+        dir_entry::FileAttributes,
+        directory::Position,
+        entry_set::EntrySet,
+        format_options::FormatOptions,
+        handle::Handle,
+        path,
+        shift::ShiftedSectors,
+        stream::Stream,
+        test_util::{format, format_with, utf16, DISK_SIZE},
+        time::{DateTime, FixedTime},
+        Error,
+    };
+
+    #[test]
+    pub fn lookup() {
+        let mut fs = format();
+        let root = fs.root_directory();
+        let heap = fs.cluster_heap();
+        let fat = *fs.fat();
+        let docs_cluster = root.first_cluster + 1;
+
+        let mut docs = EntrySet::new(
+            &utf16("Docs"),
+            FileAttributes::DIRECTORY,
+            fs.upcase_table(),
+        )
+        .unwrap();
+        docs.stream_extension.first_cluster = docs_cluster;
+        docs.stream_extension.data_length = heap.bytes_per_cluster();
+        let mut file = EntrySet::new(
+            &utf16("a.txt"),
+            FileAttributes::empty(),
+            fs.upcase_table(),
+        )
+        .unwrap();
+        let mut readme = EntrySet::new(
+            &utf16("ReadMe.TXT"),
+            FileAttributes::empty(),
+            fs.upcase_table(),
+        )
+        .unwrap();
+        let mut entries = docs.to_bytes().unwrap();
+        entries.extend(file.to_bytes().unwrap());
+        let disk = fs.boot_region_mut().disk_mut();
+        fat.set_chain(&mut *disk, [docs_cluster]).unwrap();
+        let at = |cluster, entry| Position { cluster, entry };
+        disk.write_at(at(root.first_cluster, 2).offset(heap), &entries)
+            .unwrap();
+        disk.write_at(
+            at(docs_cluster, 0).offset(heap),
+            &readme.to_bytes().unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(fs.lookup("/").unwrap(), Handle::Root(root));
+        let found = fs.lookup("/docs/README.txt").unwrap();
+        assert_eq!(
+            found,
+            Handle::Entry {
+                directory: Stream::from(&docs.stream_extension),
+                position: at(docs_cluster, 0),
+                entry_set: readme,
+            }
+        );
+        assert_eq!(found.name(), utf16("ReadMe.TXT"));
+        assert!(!found.is_dir());
+        assert_eq!(fs.lookup("DOCS/../A.TXT").unwrap().name(), utf16("a.txt"));
+        assert!(fs.lookup("/Docs/").unwrap().is_dir());
+
+        assert!(matches!(
+            fs.lookup("/docs/missing"),
+            Err(Error::Path(path::Error::NotFound))
+        ));
+        assert!(matches!(
+            fs.lookup("/a.txt/readme.txt"),
+            Err(Error::Path(path::Error::NotADirectory))
+        ));
+        assert!(matches!(fs.lookup("/docs/a:b"), Err(Error::EntrySet(_))));
+        // only the name itself matches, not a prefix of it
+        assert!(fs.lookup("/doc").is_err());
+    }
+
+    #[test]
+    pub fn time_source_from_options() {
+        let time = DateTime {
+            year: 2024,
+            month: 6,
+            day: 1,
+            ..Default::default()
+        };
+        let fs = format_with(
+            vec![0; DISK_SIZE],
+            ShiftedSectors::from(3),
+            &FormatOptions::new(FixedTime(time)),
+        );
+        assert_eq!(fs.now(), time);
+    }
+}
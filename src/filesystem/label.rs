@@ -0,0 +1,173 @@
+//! # Purpose
+//! Reads and changes the volume label, which the Volume Label directory entry
+//! of the root directory holds.
+//!
+//! A new label overwrites the entry in place, or takes the first free entry
+//! of the root directory if there was no label. Clearing the label marks the
+//! entry not in use.
+
+use alloc::vec::Vec;
+
+use crate::{
+    block_device::BlockDevice,
+    dir_entry::{DirEntry, EntryType, VolumeLabelEntry},
+    directory::{self, Entry, Position},
+    error::Error,
+    handle::Handle,
+};
+
+use super::FileSystem;
+
+impl<Disk: BlockDevice> FileSystem<Disk> {
+    /// The UTF-16 volume label, which is empty when the volume has none.
+    pub fn label(&self) -> &[u16] {
+        &self.volume_label
+    }
+
+    /// Sets the volume label to `label`, of at most 11 UTF-16 code units and
+    /// none a file name could not hold. An empty label clears it.
+    pub fn set_label(&mut self, label: &str) -> Result<(), Error> {
+        let label: Vec<u16> = label.encode_utf16().collect();
+        VolumeLabelEntry::validate(&label)?;
+        let mut bytes =
+            DirEntry::VolumeLabel(VolumeLabelEntry::new(&label)).to_bytes()?;
+        let root = self.root_directory();
+        match self.find_label()? {
+            Some(position) => {
+                if label.is_empty() {
+                    // keeps the old label, only no longer in use
+                    let old = DirEntry::VolumeLabel(VolumeLabelEntry::new(
+                        &self.volume_label,
+                    ));
+                    bytes = old.to_bytes()?;
+                    bytes[0] =
+                        EntryType::VOLUME_LABEL.with_in_use(false).to_byte();
+                }
+                directory::write_entries(
+                    self.cluster_heap,
+                    &self.fat,
+                    self.boot_region.disk_mut(),
+                    root,
+                    position,
+                    &bytes,
+                )?;
+            }
+            None if label.is_empty() => {}
+            None => {
+                self.insert_entries(&mut Handle::Root(root), &bytes)?;
+            }
+        }
+        self.boot_region.disk_mut().flush()?;
+        self.volume_label = label;
+        Ok(())
+    }
+
+    /// Where the Volume Label entry in use is in the root directory, if
+    /// there is one.
+    fn find_label(&mut self) -> Result<Option<Position>, Error> {
+        for entry in self.entries(self.root_directory())? {
+            match entry {
+                Ok((position, Entry::Other(DirEntry::VolumeLabel(_)))) => {
+                    return Ok(Some(position))
+                }
+                Err(e) if e.is_device_error() => return Err(e),
+                _ => continue,
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        block_device::BlockDevice,
+        dir_entry::{self, DirEntry, EntryType},
+        directory::Entry,
+        format_options::FormatOptions,
+        shift::ShiftedSectors,
+        test_util::{format, try_format_with, utf16, MemoryDisk, DISK_SIZE},
+        Error, FileSystem,
+    };
+
+    fn format_labelled(label: &str) -> Result<FileSystem<MemoryDisk>, Error> {
+        let options = FormatOptions {
+            volume_label: Some(label.into()),
+            ..FormatOptions::default()
+        };
+        try_format_with(vec![0; DISK_SIZE], ShiftedSectors::from(3), &options)
+    }
+
+    #[test]
+    pub fn set_and_clear() {
+        let fs = format_labelled("Camera").unwrap();
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.label(), utf16("Camera"));
+        let position = fs.find_label().unwrap().unwrap();
+        assert_eq!(position.entry, 2);
+
+        fs.set_label("Übertragung").unwrap();
+        assert_eq!(fs.find_label().unwrap(), Some(position));
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.label(), utf16("Übertragung"));
+
+        fs.set_label("").unwrap();
+        assert_eq!(fs.label(), []);
+        let mut byte = [0; 1];
+        let offset = position.offset(fs.cluster_heap());
+        let disk = fs.boot_region_mut().disk_mut();
+        disk.read_at(offset, &mut byte).unwrap();
+        assert_eq!(
+            byte[0],
+            EntryType::VOLUME_LABEL.with_in_use(false).to_byte()
+        );
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.label(), []);
+        // clearing twice finds nothing to clear
+        fs.set_label("").unwrap();
+
+        // the entry which is no longer in use is taken again
+        fs.set_label("Again").unwrap();
+        assert_eq!(fs.find_label().unwrap(), Some(position));
+        let fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.label(), utf16("Again"));
+    }
+
+    #[test]
+    pub fn add_and_validate() {
+        let mut fs = format();
+        assert_eq!(fs.label(), []);
+        fs.create_file("file").unwrap();
+        fs.set_label("NEW VOLUME").unwrap();
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.label(), utf16("NEW VOLUME"));
+        // the label goes after the entry set of the file
+        let labels: Vec<_> = fs
+            .entries(fs.root_directory())
+            .unwrap()
+            .filter_map(|entry| match entry.unwrap() {
+                (position, Entry::Other(DirEntry::VolumeLabel(label))) => {
+                    Some((position.entry, label.label().to_vec()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(labels, [(5, utf16("NEW VOLUME"))]);
+
+        assert!(matches!(
+            fs.set_label("twelve chars"),
+            Err(Error::DirEntry(dir_entry::Error::LabelTooLong(12)))
+        ));
+        for invalid in ["a:b", "tab\t", "x?"] {
+            assert!(matches!(
+                fs.set_label(invalid),
+                Err(Error::DirEntry(dir_entry::Error::InvalidLabel))
+            ));
+        }
+        assert_eq!(fs.label(), utf16("NEW VOLUME"));
+        assert!(matches!(
+            format_labelled("far too long a label"),
+            Err(Error::DirEntry(dir_entry::Error::LabelTooLong(20)))
+        ));
+    }
+}
@@ -0,0 +1,678 @@
+//! # Purpose
+//! Creates, removes and renames files and directories, and changes their
+//! attributes.
+//!
+//! Removed entry sets have InUse cleared in each of their entries rather
+//! than being zeroed, and the clusters they pointed to are freed in both the
+//! FAT and the Allocation Bitmap. Every change stamps the directories it is
+//! made in as modified.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    block_device::BlockDevice,
+    dir_entry::{DirEntry, FileAttributes},
+    directory::{self, position_at, Entries, Position},
+    entry_set::{self, EntrySet},
+    error::Error,
+    fat_entry::{FatEntry, Link},
+    file::File,
+    handle::Handle,
+    path::{self, Component},
+    stream::{Clusters, Stream},
+};
+
+use super::FileSystem;
+
+impl<Disk: BlockDevice> FileSystem<Disk> {
+    /// Creates an empty file at `path` and opens it for writing. The file
+    /// has the Archive attribute, as a new file has not been backed up.
+    pub fn create_file(&mut self, path: &str) -> Result<File<'_, Disk>, Error> {
+        let (directory, position, entry_set) =
+            self.create(path, FileAttributes::ARCHIVE)?;
+        File::new(self, directory, position, entry_set, true)
+    }
+
+    /// Creates an empty directory at `path`.
+    pub fn create_dir(&mut self, path: &str) -> Result<Handle, Error> {
+        let (directory, position, entry_set) =
+            self.create(path, FileAttributes::DIRECTORY)?;
+        Ok(Handle::Entry {
+            directory,
+            position,
+            entry_set,
+        })
+    }
+
+    /// Removes the file at `path` and frees its clusters.
+    pub fn remove_file(&mut self, path: &str) -> Result<(), Error> {
+        let (mut parents, handle) = self.resolve_entry(path)?;
+        if handle.is_dir() {
+            return Err(path::Error::IsADirectory.into());
+        }
+        self.remove(parents.last_mut().expect("there is a parent"), handle)
+    }
+
+    /// Removes the directory at `path`, which has to be empty.
+    pub fn remove_dir(&mut self, path: &str) -> Result<(), Error> {
+        let (mut parents, handle) = self.resolve_entry(path)?;
+        if !handle.is_dir() {
+            return Err(path::Error::NotADirectory.into());
+        }
+        // malformed entry sets count as well, so that nothing is lost
+        if self.entries(handle.stream())?.next().is_some() {
+            return Err(path::Error::NotEmpty.into());
+        }
+        self.remove(parents.last_mut().expect("there is a parent"), handle)
+    }
+
+    /// Moves the file or directory at `from` to `to`, which may be in
+    /// another directory. Only the entry set moves, the data stays where it
+    /// is.
+    pub fn rename(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let (mut from_parents, handle) = self.resolve_entry(from)?;
+        let Handle::Entry {
+            directory,
+            position,
+            entry_set: mut old,
+        } = handle
+        else {
+            unreachable!("the root directory has no parent");
+        };
+        let (mut to_parents, name) = self.resolve_parent(to)?;
+        if to_parents
+            .iter()
+            .any(|parent| parent.position() == Some(position))
+        {
+            return Err(path::Error::InsideItself.into());
+        }
+        let to_parent = to_parents.last_mut().expect("there is a parent");
+        // renaming to a name differing only in case finds the file itself
+        if let Some(existing) = self.find(to_parent.stream(), &name)? {
+            if existing.position() != Some(position) {
+                return Err(path::Error::AlreadyExists.into());
+            }
+        }
+        let mut renamed = old.clone();
+        renamed.set_name(&name, &self.upcase_table)?;
+
+        let same_directory =
+            to_parent.stream().first_cluster == directory.first_cluster;
+        if same_directory && renamed.len() <= old.len() {
+            // in place, with the entries the shorter name frees deleted
+            let mut bytes = renamed.to_bytes()?;
+            bytes.extend_from_slice(
+                &old.to_deleted_bytes()?[renamed.len() * DirEntry::SIZE..],
+            );
+            directory::write_entries(
+                self.cluster_heap,
+                &self.fat,
+                self.boot_region.disk_mut(),
+                directory,
+                position,
+                &bytes,
+            )?;
+        } else {
+            self.insert(to_parent, &mut renamed)?;
+            self.delete_entry_set(directory, position, &mut old)?;
+        }
+
+        self.touch(to_parent)?;
+        if !same_directory {
+            self.touch(from_parents.last_mut().expect("there is a parent"))?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the attributes of the file or directory at `path`, which is
+    /// allowed even if it is ReadOnly. Whether it is a directory cannot
+    /// change, so the Directory attribute of `attributes` is ignored.
+    pub fn set_attributes(
+        &mut self,
+        path: &str,
+        mut attributes: FileAttributes,
+    ) -> Result<(), Error> {
+        let (_, handle) = self.resolve_entry(path)?;
+        let Handle::Entry {
+            directory,
+            position,
+            mut entry_set,
+        } = handle
+        else {
+            unreachable!("the root directory has no parent");
+        };
+        attributes
+            .set(FileAttributes::DIRECTORY, entry_set.file.is_directory());
+        entry_set.file.file_attributes = attributes;
+        let bytes = entry_set.to_bytes()?;
+        directory::write_entries(
+            self.cluster_heap,
+            &self.fat,
+            self.boot_region.disk_mut(),
+            directory,
+            position,
+            &bytes,
+        )?;
+        self.boot_region.disk_mut().flush()
+    }
+
+    /// Resolves the directories leading up to the last component of `path`,
+    /// returning them and the name the last component gives.
+    fn resolve_parent(
+        &mut self,
+        path: &str,
+    ) -> Result<(Vec<Handle>, Vec<u16>), Error> {
+        let mut components: Vec<Component> = path::components(path).collect();
+        let Some(Component::Name(name)) = components.pop() else {
+            return Err(entry_set::Error::InvalidName.into());
+        };
+        entry_set::validate_name(&name)?;
+        let parents = self.resolve(components)?;
+        if !parents.last().expect("the root is never popped").is_dir() {
+            return Err(path::Error::NotADirectory.into());
+        }
+        Ok((parents, name))
+    }
+
+    /// Resolves `path`, which cannot be the root directory, returning the
+    /// directories leading up to it and what it leads to.
+    fn resolve_entry(
+        &mut self,
+        path: &str,
+    ) -> Result<(Vec<Handle>, Handle), Error> {
+        let (parents, name) = self.resolve_parent(path)?;
+        let parent = parents.last().expect("the root is never popped");
+        let handle = self
+            .find(parent.stream(), &name)?
+            .ok_or(path::Error::NotFound)?;
+        Ok((parents, handle))
+    }
+
+    /// Adds an entry set with the given attributes to the directory `path`
+    /// leads to, returning the directory, where the entry set is and the
+    /// entry set.
+    fn create(
+        &mut self,
+        path: &str,
+        file_attributes: FileAttributes,
+    ) -> Result<(Stream, Position, EntrySet), Error> {
+        let (mut parents, name) = self.resolve_parent(path)?;
+        let parent = parents.last_mut().expect("the root is never popped");
+        if self.find(parent.stream(), &name)?.is_some() {
+            return Err(path::Error::AlreadyExists.into());
+        }
+        let mut entry_set =
+            EntrySet::new(&name, file_attributes, &self.upcase_table)?;
+        let now = self.timestamp();
+        entry_set.file.set_created(now);
+        entry_set.file.set_modified(now);
+        entry_set.file.set_accessed(now);
+        if entry_set.file.is_directory() {
+            // a directory has at least one cluster, whose first entry marks
+            // its end
+            let cluster = self.allocate(FatEntry::FIRST_CLUSTER, 1)?[0];
+            self.zero_clusters(&[cluster])?;
+            let stream_extension = &mut entry_set.stream_extension;
+            stream_extension.first_cluster = cluster;
+            stream_extension
+                .general_secondary_flags
+                .set_no_fat_chain(true);
+            stream_extension.data_length =
+                self.cluster_heap.bytes_per_cluster();
+            stream_extension.valid_data_length = stream_extension.data_length;
+        }
+        let position = match self.insert(parent, &mut entry_set) {
+            Ok(position) => position,
+            Err(error) => {
+                if entry_set.file.is_directory() {
+                    let cluster = entry_set.stream_extension.first_cluster;
+                    self.free_clusters(&Clusters::Contiguous(
+                        cluster..cluster + 1,
+                    ))?;
+                }
+                return Err(error);
+            }
+        };
+        self.touch(parent)?;
+        Ok((parent.stream(), position, entry_set))
+    }
+
+    /// Removes the entry set of `handle` from `parent` and frees the
+    /// clusters of `handle`.
+    fn remove(
+        &mut self,
+        parent: &mut Handle,
+        handle: Handle,
+    ) -> Result<(), Error> {
+        let Handle::Entry {
+            directory,
+            position,
+            mut entry_set,
+        } = handle
+        else {
+            unreachable!("the root directory has no parent");
+        };
+        // the entry set goes first, so that an interruption leaks clusters
+        // rather than leaving a file pointing to free ones
+        self.delete_entry_set(directory, position, &mut entry_set)?;
+        self.free(Stream::from(&entry_set.stream_extension))?;
+        self.touch(parent)
+    }
+
+    /// Writes `entry_set` into entries of `directory` which are not in use,
+    /// growing the directory when there are not enough of them in a row.
+    fn insert(
+        &mut self,
+        directory: &mut Handle,
+        entry_set: &mut EntrySet,
+    ) -> Result<Position, Error> {
+        let bytes = entry_set.to_bytes()?;
+        self.insert_entries(directory, &bytes)
+    }
+
+    /// Writes the entries `bytes` holds into entries of `directory` which
+    /// are not in use, growing the directory when there are not enough of
+    /// them in a row.
+    pub(super) fn insert_entries(
+        &mut self,
+        directory: &mut Handle,
+        bytes: &[u8],
+    ) -> Result<Position, Error> {
+        let count = (bytes.len() / DirEntry::SIZE) as u64;
+        let (start, length) = Entries::new(
+            self.cluster_heap,
+            &self.fat,
+            self.boot_region.disk_mut(),
+            directory.stream(),
+        )?
+        .find_free(count)?;
+        if length < count {
+            let per_cluster =
+                self.cluster_heap.bytes_per_cluster() / DirEntry::SIZE as u64;
+            let clusters = (count - length).div_ceil(per_cluster) as u32;
+            self.grow_directory(directory, clusters)?;
+        }
+        let clusters = directory.stream().clusters(
+            self.cluster_heap,
+            &self.fat,
+            self.boot_region.disk_mut(),
+        )?;
+        let position = position_at(self.cluster_heap, &clusters, start)
+            .expect("the directory was grown to hold the entries");
+        directory::write_entries(
+            self.cluster_heap,
+            &self.fat,
+            self.boot_region.disk_mut(),
+            directory.stream(),
+            position,
+            bytes,
+        )?;
+        Ok(position)
+    }
+
+    /// Appends `clusters` zeroed clusters to `directory`.
+    fn grow_directory(
+        &mut self,
+        directory: &mut Handle,
+        clusters: u32,
+    ) -> Result<(), Error> {
+        match directory {
+            Handle::Root(stream) => {
+                let chain = stream.clusters(
+                    self.cluster_heap,
+                    &self.fat,
+                    self.boot_region.disk_mut(),
+                )?;
+                let last = *chain.last().expect("the root has a cluster");
+                let new = self.allocate(last + 1, clusters)?;
+                self.zero_clusters(&new)?;
+                self.fat.set_chain(
+                    self.boot_region.disk_mut(),
+                    [last].into_iter().chain(new),
+                )?;
+            }
+            Handle::Entry {
+                directory,
+                position,
+                entry_set,
+            } => {
+                let bytes_per_cluster = self.cluster_heap.bytes_per_cluster();
+                let mut file = File::new(
+                    self,
+                    *directory,
+                    *position,
+                    entry_set.clone(),
+                    true,
+                )?;
+                let end = file.len();
+                let zeros =
+                    vec![0; (clusters as u64 * bytes_per_cluster) as usize];
+                file.write_at(end, &zeros)?;
+                file.sync()?;
+                *entry_set = file.entry_set().clone();
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills `clusters` with zeros.
+    fn zero_clusters(&mut self, clusters: &[u32]) -> Result<(), Error> {
+        let zeros = vec![0; self.cluster_heap.bytes_per_cluster() as usize];
+        for cluster in clusters {
+            self.boot_region
+                .disk_mut()
+                .write_at(self.cluster_heap.cluster_offset(*cluster), &zeros)?;
+        }
+        Ok(())
+    }
+
+    /// Clears InUse in the entries of `entry_set`, which is at `position` in
+    /// `directory`.
+    fn delete_entry_set(
+        &mut self,
+        directory: Stream,
+        position: Position,
+        entry_set: &mut EntrySet,
+    ) -> Result<(), Error> {
+        let bytes = entry_set.to_deleted_bytes()?;
+        directory::write_entries(
+            self.cluster_heap,
+            &self.fat,
+            self.boot_region.disk_mut(),
+            directory,
+            position,
+            &bytes,
+        )
+    }
+
+    /// Stamps `directory` as modified, unless it is the root directory,
+    /// which has no timestamps.
+    fn touch(&mut self, directory: &mut Handle) -> Result<(), Error> {
+        if let Handle::Entry {
+            directory,
+            position,
+            entry_set,
+        } = directory
+        {
+            let now = self.timestamp();
+            entry_set.file.set_modified(now);
+            entry_set.file.set_accessed(now);
+            let bytes = entry_set.to_bytes()?;
+            directory::write_entries(
+                self.cluster_heap,
+                &self.fat,
+                self.boot_region.disk_mut(),
+                *directory,
+                *position,
+                &bytes,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Frees the clusters of `stream` in both the FAT and the Allocation
+    /// Bitmap.
+    pub(crate) fn free(&mut self, stream: Stream) -> Result<(), Error> {
+        let disk = self.boot_region.disk_mut();
+        let clusters = stream.locate(self.cluster_heap, &self.fat, disk)?;
+        self.free_clusters(&clusters)
+    }
+
+    /// Frees `clusters`, clearing their FAT entries if they are chained.
+    pub(crate) fn free_clusters(
+        &mut self,
+        clusters: &Clusters,
+    ) -> Result<(), Error> {
+        let disk = self.boot_region.disk_mut();
+        let mut index = 0;
+        while let Some(first) = clusters.get(index) {
+            let run = clusters.run(index);
+            if let Clusters::Chain(_) = clusters {
+                for cluster in first..first + run as u32 {
+                    self.fat.set_entry(disk, cluster, Link::Free)?;
+                }
+            }
+            self.allocation_bitmap.set(disk, first, run as u32, false)?;
+            index += run;
+        }
+        disk.flush()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::{Read, Write};
+
+    use crate::{
+        allocation_bitmap,
+        directory::Entry,
+        fat_entry::FatEntry,
+        path,
+        test_util::{format, utf16},
+        time::{DateTime, FixedTime},
+        Error, FileSystem,
+    };
+
+    fn path_error(result: Result<impl Sized, Error>) -> path::Error {
+        match result {
+            Err(Error::Path(e)) => e,
+            Err(e) => panic!("{}", e),
+            Ok(_) => panic!("the operation should fail"),
+        }
+    }
+
+    fn read(
+        fs: &mut FileSystem<impl crate::BlockDevice>,
+        path: &str,
+    ) -> Vec<u8> {
+        let mut data = Vec::new();
+        fs.open(path).unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    pub fn create_and_grow() {
+        let mut fs = format();
+        fs.create_dir("/dir").unwrap();
+        fs.create_file("/dir/hello.txt")
+            .unwrap()
+            .write_all(b"hello")
+            .unwrap();
+        assert_eq!(
+            path_error(fs.create_dir("/DIR")),
+            path::Error::AlreadyExists
+        );
+        assert_eq!(
+            path_error(fs.create_file("/dir/hello.txt/x")),
+            path::Error::NotADirectory
+        );
+        assert_eq!(
+            path_error(fs.create_file("/missing/x")),
+            path::Error::NotFound
+        );
+        assert!(matches!(fs.create_dir("/"), Err(Error::EntrySet(_))));
+
+        // 3 entries each, so 50 files need a second cluster in both the root
+        // and a subdirectory
+        for i in 0..50 {
+            fs.create_file(&format!("/dir/file {}", i)).unwrap();
+            fs.create_file(&format!("/file {}", i)).unwrap();
+        }
+        let cluster_size = fs.cluster_heap().bytes_per_cluster();
+        let dir = fs.lookup("/dir").unwrap();
+        assert_eq!(dir.len(), 2 * cluster_size);
+
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(read(&mut fs, "/Dir/Hello.TXT"), b"hello");
+        for i in 0..50 {
+            fs.lookup(&format!("/dir/file {}", i)).unwrap();
+            fs.lookup(&format!("/file {}", i)).unwrap();
+        }
+        let root = fs.root_directory();
+        let root_clusters: Vec<u32> = fs
+            .cluster_chain(root.first_cluster)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(root_clusters.len(), 2);
+    }
+
+    #[test]
+    pub fn create_dir_when_full() {
+        let mut fs = format();
+        fs.create_dir("/dir").unwrap();
+        // 3 entries each, so the 43rd file would need a second cluster
+        let entries = fs.cluster_heap().bytes_per_cluster() / 32;
+        for i in 0..entries / 3 {
+            fs.create_file(&format!("/dir/file {}", i)).unwrap();
+        }
+        let free = fs.free_cluster_count();
+        fs.allocate(FatEntry::FIRST_CLUSTER, free - 1).unwrap();
+
+        // the cluster of the new directory is freed again when its entry set
+        // does not fit
+        assert!(matches!(
+            fs.create_dir("/dir/sub"),
+            Err(Error::AllocationBitmap(
+                allocation_bitmap::Error::Full { .. }
+            ))
+        ));
+        assert_eq!(fs.free_cluster_count(), 1);
+        assert_eq!(path_error(fs.lookup("/dir/sub")), path::Error::NotFound);
+    }
+
+    #[test]
+    pub fn remove() {
+        let mut fs = format();
+        let free = fs.free_cluster_count();
+        fs.create_dir("/dir").unwrap();
+        let data = vec![7; 3 * fs.cluster_heap().bytes_per_cluster() as usize];
+        fs.create_file("/dir/data")
+            .unwrap()
+            .write_all(&data)
+            .unwrap();
+        assert_eq!(fs.free_cluster_count(), free - 4);
+
+        assert_eq!(path_error(fs.remove_dir("/dir")), path::Error::NotEmpty);
+        assert_eq!(
+            path_error(fs.remove_file("/dir")),
+            path::Error::IsADirectory
+        );
+        assert_eq!(
+            path_error(fs.remove_dir("/dir/data")),
+            path::Error::NotADirectory
+        );
+        fs.remove_file("/dir/data").unwrap();
+        assert_eq!(path_error(fs.open("/dir/data")), path::Error::NotFound);
+        // the entry set is still there, only not in use
+        let dir = fs.lookup("/dir").unwrap().stream();
+        let deleted: Vec<_> = fs
+            .entries(dir)
+            .unwrap()
+            .include_deleted(true)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert!(matches!(
+            &deleted[..],
+            [(_, Entry::File(set))] if set.name() == utf16("data")
+        ));
+        assert!(!deleted[0].1.in_use());
+        fs.remove_dir("/dir").unwrap();
+        assert_eq!(fs.free_cluster_count(), free);
+
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.free_cluster_count(), free);
+        assert_eq!(path_error(fs.lookup("/dir")), path::Error::NotFound);
+        // the freed slots are reused
+        fs.create_file("/again").unwrap();
+        assert_eq!(
+            fs.lookup("/again").unwrap().position(),
+            Some(crate::directory::Position {
+                cluster: fs.root_directory().first_cluster,
+                entry: 2
+            })
+        );
+    }
+
+    #[test]
+    pub fn rename() {
+        let mut fs = format();
+        fs.create_dir("/a").unwrap();
+        fs.create_dir("/a/b").unwrap();
+        fs.create_file("/a/file")
+            .unwrap()
+            .write_all(b"data")
+            .unwrap();
+        fs.create_file("/other").unwrap();
+
+        // a longer name needs more entries, so the entry set moves
+        let long = "a name which is longer than fifteen code units";
+        fs.rename("/a/file", &format!("/a/{}", long)).unwrap();
+        assert_eq!(read(&mut fs, &format!("/a/{}", long)), b"data");
+        fs.rename(&format!("/a/{}", long), "/a/f").unwrap();
+        fs.rename("/a/f", "/a/F").unwrap();
+        assert_eq!(fs.lookup("/a/f").unwrap().name(), utf16("F"));
+        fs.rename("/a/F", "/a/b/moved").unwrap();
+        assert_eq!(read(&mut fs, "/a/b/moved"), b"data");
+        assert_eq!(path_error(fs.lookup("/a/F")), path::Error::NotFound);
+        fs.rename("/a/b", "/b").unwrap();
+        assert_eq!(read(&mut fs, "/b/moved"), b"data");
+
+        assert_eq!(
+            path_error(fs.rename("/b/moved", "/other")),
+            path::Error::AlreadyExists
+        );
+        assert_eq!(
+            path_error(fs.rename("/a", "/a/inside")),
+            path::Error::InsideItself
+        );
+        fs.create_dir("/a/c").unwrap();
+        assert_eq!(
+            path_error(fs.rename("/a", "/a/c/inside")),
+            path::Error::InsideItself
+        );
+
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(read(&mut fs, "/B/MOVED"), b"data");
+        let names: Vec<String> = fs
+            .entries(fs.root_directory())
+            .unwrap()
+            .filter_map(|entry| match entry.unwrap().1 {
+                Entry::File(set) => {
+                    Some(String::from_utf16(set.name()).unwrap())
+                }
+                Entry::Other(_) => None,
+            })
+            .collect();
+        assert_eq!(names, ["a", "other", "b"]);
+    }
+
+    #[test]
+    pub fn parent_timestamps() {
+        let mut fs = format();
+        let at = |minute| DateTime {
+            year: 2025,
+            minute,
+            ..Default::default()
+        };
+        fs.set_time_source(FixedTime(at(1)));
+        fs.create_dir("/dir").unwrap();
+        let modified = |fs: &mut FileSystem<_>| {
+            let dir = fs.lookup("/dir").unwrap();
+            let file = dir.entry_set().unwrap().file;
+            (
+                file.created().unwrap().date_time(),
+                file.modified().unwrap().date_time(),
+            )
+        };
+        assert_eq!(modified(&mut fs), (at(1), at(1)));
+        fs.set_time_source(FixedTime(at(2)));
+        fs.create_file("/dir/file").unwrap();
+        assert_eq!(modified(&mut fs), (at(1), at(2)));
+        fs.set_time_source(FixedTime(at(3)));
+        fs.rename("/dir/file", "/dir/renamed").unwrap();
+        assert_eq!(modified(&mut fs).1, at(3));
+        fs.set_time_source(FixedTime(at(4)));
+        fs.remove_file("/dir/renamed").unwrap();
+        assert_eq!(modified(&mut fs).1, at(4));
+    }
+}
@@ -0,0 +1,120 @@
+//! # Purpose
+//! Defines [`FormatOptions`], the choices made when formatting a volume that
+//! its geometry does not dictate. Everything which would otherwise differ
+//! between two formats of the same disk, like the serial number, can be
+//! pinned here to produce byte-identical images.
+
+use alloc::string::String;
+
+use uguid::Guid;
+
+use crate::time::{DateTime, FixedTime, TimeSource};
+
+pub struct FormatOptions<Time> {
+    /// Where the date and time of formatting comes from.
+    pub time_source: Time,
+    /// The VolumeSerialNumber of the boot sector. When `None` it is derived
+    /// from the date and time of formatting.
+    pub volume_serial_number: Option<u32>,
+    /// The label recorded in the Volume Label directory entry of the root
+    /// directory, at most 11 UTF-16 code units. When `None` the volume has
+    /// no label.
+    pub volume_label: Option<String>,
+    /// The GUID recorded in the Volume GUID directory entry of the root
+    /// directory. When `None` the volume has no GUID.
+    pub volume_guid: Option<Guid>,
+    /// 1 for a plain exFAT volume, 2 for a TexFAT volume with a Second FAT
+    /// and Second Allocation Bitmap.
+    pub number_of_fats: u8,
+    /// The number of sectors, a power of two, which the First FAT and the
+    /// Cluster Heap are aligned to. When `None` they are aligned to a
+    /// cluster.
+    pub alignment: Option<u32>,
+}
+
+impl<Time: TimeSource> FormatOptions<Time> {
+    /// Options which take the time of formatting from `time_source` and
+    /// otherwise leave everything at its default.
+    pub fn new(time_source: Time) -> Self {
+        Self {
+            time_source,
+            volume_serial_number: None,
+            volume_label: None,
+            volume_guid: None,
+            number_of_fats: 1,
+            alignment: None,
+        }
+    }
+
+    /// The serial number to give the volume.
+    pub fn volume_serial_number(&self) -> u32 {
+        self.volume_serial_number
+            .unwrap_or_else(|| serial_number(&self.time_source.now()))
+    }
+}
+
+impl FormatOptions<FixedTime> {
+    /// Options under which formatting the same disk with the same parameters
+    /// always produces the same bytes.
+    pub fn reproducible(
+        time: DateTime,
+        volume_serial_number: u32,
+        volume_guid: Guid,
+    ) -> Self {
+        Self {
+            volume_serial_number: Some(volume_serial_number),
+            volume_guid: Some(volume_guid),
+            ..Self::new(FixedTime(time))
+        }
+    }
+}
+
+impl Default for FormatOptions<FixedTime> {
+    fn default() -> Self {
+        Self::new(FixedTime::default())
+    }
+}
+
+/// Combines the date and time of formatting into a VolumeSerialNumber the way
+/// MS-DOS did: the high word is the month and day plus the seconds and
+/// hundredths, the low word is the hours and minutes plus the year.
+fn serial_number(now: &DateTime) -> u32 {
+    let hundredths = (now.millisecond / 10) as u8;
+    let high = u16::from_be_bytes([now.month, now.day])
+        .wrapping_add(u16::from_be_bytes([now.second, hundredths]));
+    let low = u16::from_be_bytes([now.hour, now.minute]).wrapping_add(now.year);
+    (high as u32) << 16 | low as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use uguid::guid;
+
+    use crate::time::{DateTime, FixedTime};
+
+    use super::FormatOptions;
+
+    #[test]
+    pub fn serial_number_from_time() {
+        let time = DateTime {
+            year: 2024,
+            month: 11,
+            day: 30,
+            hour: 13,
+            minute: 45,
+            second: 7,
+            millisecond: 890,
+        };
+        // 0x0B1E + 0x0759, 0x0D2D + 0x07E8
+        assert_eq!(
+            FormatOptions::new(FixedTime(time)).volume_serial_number(),
+            0x1277_1515
+        );
+        let reproducible = FormatOptions::reproducible(
+            time,
+            0x1234_5678,
+            guid!("01234567-89ab-cdef-0123-456789abcdef"),
+        );
+        assert_eq!(reproducible.volume_serial_number(), 0x1234_5678);
+    }
+}
@@ -0,0 +1,86 @@
+//! # Purpose
+//! Refers to a file or directory found in the directory tree, by where its
+//! entry set is and what it holds.
+
+use crate::{
+    dir_entry::FileAttributes, directory::Position, entry_set::EntrySet,
+    stream::Stream,
+};
+
+/// A file or directory of the volume, as found by
+/// [`crate::FileSystem::lookup`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Handle {
+    /// The root directory, which has no entry set.
+    Root(Stream),
+    /// A file or directory described by an entry set.
+    Entry {
+        /// The directory holding the entry set.
+        directory: Stream,
+        /// Where the entry set starts within `directory`.
+        position: Position,
+        entry_set: EntrySet,
+    },
+}
+
+impl Handle {
+    /// Where the data of the file or directory is.
+    pub fn stream(&self) -> Stream {
+        match self {
+            Handle::Root(stream) => *stream,
+            Handle::Entry { entry_set, .. } => {
+                Stream::from(&entry_set.stream_extension)
+            }
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        match self {
+            Handle::Root(_) => true,
+            Handle::Entry { entry_set, .. } => entry_set.file.is_directory(),
+        }
+    }
+
+    /// The attributes of the file or directory. The root directory has no
+    /// entry set to record any, and only counts as a directory.
+    pub fn attributes(&self) -> FileAttributes {
+        match self {
+            Handle::Root(_) => FileAttributes::DIRECTORY,
+            Handle::Entry { entry_set, .. } => entry_set.file.file_attributes,
+        }
+    }
+
+    /// The entry set, unless this is the root directory.
+    pub fn entry_set(&self) -> Option<&EntrySet> {
+        match self {
+            Handle::Root(_) => None,
+            Handle::Entry { entry_set, .. } => Some(entry_set),
+        }
+    }
+
+    /// Where the entry set starts within its directory, unless this is the
+    /// root directory.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Handle::Root(_) => None,
+            Handle::Entry { position, .. } => Some(*position),
+        }
+    }
+
+    /// The UTF-16 name, which is empty for the root directory.
+    pub fn name(&self) -> &[u16] {
+        self.entry_set().map(EntrySet::name).unwrap_or_default()
+    }
+
+    /// The number of bytes of data, which is 0 for the root directory as its
+    /// length is only given by its cluster chain.
+    pub fn len(&self) -> u64 {
+        self.entry_set()
+            .map(|set| set.stream_extension.data_length)
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -1,7 +1,35 @@
-// #![cfg_attr(not(test), no_std)]
-mod boot_region;
-mod fat_entry;
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+pub mod allocation_bitmap;
+pub mod block_device;
+pub mod boot_region;
+pub mod cluster_heap;
+pub mod dir_entry;
+pub mod directory;
+pub mod entry_set;
+mod error;
+pub mod fat;
+pub mod fat_entry;
+pub mod file;
 mod filesystem;
-mod oem;
-mod shift;
-mod super_block;
+pub mod format_options;
+pub mod handle;
+pub mod oem;
+pub mod path;
+pub mod root_directory;
+pub mod shift;
+pub mod stream;
+pub mod super_block;
+#[cfg(test)]
+mod test_util;
+pub mod time;
+pub mod upcase_table;
+
+pub use block_device::BlockDevice;
+#[cfg(feature = "std")]
+pub use block_device::IoDevice;
+pub use boot_region::BootRegion;
+pub use error::Error;
+pub use filesystem::FileSystem;
+pub use format_options::FormatOptions;
@@ -1,10 +1,12 @@
 // 3.3 - https://learn.microsoft.com/en-us/windows/win32/fileio/exfat-specification#33-main-and-backup-oem-parameters-sub-regions
 
-mod flash;
+pub mod flash;
 
 use flash::{flash_guid, FlashParameter};
 use serde::{de::Visitor, ser::SerializeTuple, Deserialize, Serialize};
 use uguid::Guid;
+
+use crate::error::Error;
 #[derive(Serialize, Deserialize)]
 pub struct UnknownParameter {
     #[serde(skip)]
@@ -31,7 +33,7 @@ impl UnknownParameter {
     }
 }
 
-enum Parameter {
+pub enum Parameter {
     Null,
     Flash(FlashParameter),
     Unknown(UnknownParameter),
@@ -92,16 +94,30 @@ impl<'de> Deserialize<'de> for Parameter {
             where
                 A: serde::de::SeqAccess<'de>,
             {
-                let guid_bytes: [u8; 16] = seq.next_element()?.expect("A Guid");
+                let guid_bytes: [u8; 16] = seq
+                    .next_element()?
+                    .ok_or(serde::de::Error::invalid_length(0, &self))?;
                 let guid = Guid::from_bytes(guid_bytes);
                 if guid == flash_guid() {
-                    let flash: FlashParameter =
-                        seq.next_element()?.expect("custom_defined");
+                    let flash: FlashParameter = seq
+                        .next_element()?
+                        .ok_or(serde::de::Error::invalid_length(1, &self))?;
                     Ok(Parameter::Flash(flash))
-                } else if guid.is_zero() {
-                    Ok(Parameter::Null)
                 } else {
-                    Ok(Parameter::Unknown(UnknownParameter::NULL))
+                    // every parameter is exactly 48 bytes long, so the
+                    // custom defined bytes have to be consumed even when
+                    // they carry no meaning to us.
+                    let custom_defined: [u8; 32] = seq
+                        .next_element()?
+                        .ok_or(serde::de::Error::invalid_length(1, &self))?;
+                    if guid.is_zero() {
+                        Ok(Parameter::Null)
+                    } else {
+                        Ok(Parameter::Unknown(UnknownParameter::new(
+                            guid,
+                            custom_defined,
+                        )))
+                    }
                 }
             }
         }
@@ -115,26 +131,54 @@ pub struct Oem {
     parameters: [Parameter; 10],
 }
 
+impl Default for Oem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Oem {
     pub fn new() -> Self {
         Self {
             parameters: [Parameter::NULL; 10],
         }
     }
-    pub fn insert<Param>(&mut self, param: Param, index: usize)
+    pub fn insert<Param>(
+        &mut self,
+        param: Param,
+        index: usize,
+    ) -> Result<(), Error>
     where
         Param: Into<Parameter>,
     {
-        self.parameters[index] = param.into();
+        let slot = self
+            .parameters
+            .get_mut(index)
+            .ok_or(Error::ParameterIndex(index))?;
+        *slot = param.into();
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{flash::FlashOptions, FlashParameter};
+    use super::{flash::FlashOptions, FlashParameter, Oem, Parameter};
 
     #[test]
     pub fn test() {
-        let param = FlashParameter::new(FlashOptions::default());
+        let param = FlashParameter::new(FlashOptions {
+            erase_block_size: 4096,
+            ..Default::default()
+        });
+        let mut oem = Oem::new();
+        oem.insert(param, 1).unwrap();
+        let options = bincode::config::legacy();
+        let serialized = bincode::serde::encode_to_vec(&oem, options).unwrap();
+        // ten parameters of 48 bytes each
+        assert_eq!(serialized.len(), 480);
+        let (deserialized, _): (Oem, _) =
+            bincode::serde::decode_from_slice(&serialized, options).unwrap();
+        assert!(matches!(deserialized.parameters[0], Parameter::Null));
+        assert!(matches!(deserialized.parameters[1], Parameter::Flash(_)));
     }
 }
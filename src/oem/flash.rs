@@ -1,6 +1,4 @@
-use serde::{
-    ser::SerializeTuple, Deserialize, Deserializer, Serialize, Serializer,
-};
+use serde::{Deserialize, Serialize};
 use uguid::{guid, Guid};
 
 use super::Parameter;
@@ -49,6 +47,9 @@ pub struct FlashParameter {
     /// The WriteCycle field shall describe the average write cycle time, in
     /// nanoseconds.
     write_cycle: u32,
+    /// The Reserved field pads the parameter out to the 32 bytes every
+    /// parameter's CustomDefined field occupies.
+    reserved: [u8; 4],
 }
 #[derive(Default)]
 pub struct FlashOptions {
@@ -95,6 +96,7 @@ impl From<FlashOptions> for FlashParameter {
             programming_time,
             read_cycle,
             write_cycle,
+            reserved: [0; 4],
         }
     }
 }
@@ -0,0 +1,90 @@
+//! # Purpose
+//! Splits paths such as `/a/b/c.txt` into the names of the directories and
+//! the file they lead through.
+//!
+//! Paths are always resolved from the root directory, so a leading `/` is
+//! optional. Empty components and `.` are ignored, and `..` goes back to the
+//! parent directory.
+
+use core::fmt::Display;
+
+use alloc::vec::Vec;
+
+/// Ways a path can fail to lead to a file or directory.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// No file or directory of the given name exists.
+    NotFound,
+    /// A file was given where a directory was expected, such as a component
+    /// of a path before the last one.
+    NotADirectory,
+    /// A directory was given where a file was expected.
+    IsADirectory,
+    /// A file or directory of the given name exists already.
+    AlreadyExists,
+    /// A directory to be removed still holds files or directories.
+    NotEmpty,
+    /// A directory cannot be moved into itself or one of its descendants.
+    InsideItself,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::NotFound => write!(f, "No such file or directory."),
+            Error::NotADirectory => write!(f, "Not a directory."),
+            Error::IsADirectory => write!(f, "Is a directory."),
+            Error::AlreadyExists => write!(f, "File exists."),
+            Error::NotEmpty => write!(f, "Directory not empty."),
+            Error::InsideItself => {
+                write!(f, "Cannot move a directory into itself.")
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// A component of a path.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Component {
+    /// `..`, the parent directory.
+    Parent,
+    /// The UTF-16 name of a file or directory.
+    Name(Vec<u16>),
+}
+
+/// The components of `path` in order.
+pub fn components(path: &str) -> impl Iterator<Item = Component> + '_ {
+    path.split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .map(|component| match component {
+            ".." => Component::Parent,
+            name => Component::Name(name.encode_utf16().collect()),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{components, Component};
+
+    fn name(name: &str) -> Component {
+        Component::Name(name.encode_utf16().collect())
+    }
+
+    #[test]
+    pub fn split() {
+        assert_eq!(
+            components("/a/b/c.txt").collect::<Vec<_>>(),
+            [name("a"), name("b"), name("c.txt")]
+        );
+        assert_eq!(
+            components("a//./b/../ü/").collect::<Vec<_>>(),
+            [name("a"), name("b"), Component::Parent, name("ü")]
+        );
+        assert_eq!(components("/").count(), 0);
+        assert_eq!(components("").count(), 0);
+    }
+}
@@ -0,0 +1,194 @@
+//! # Purpose
+//! Writes the root directory of a new volume and finds the critical primary
+//! directory entries, those for the Allocation Bitmap and the Up-case Table,
+//! which only ever appear in the root directory, along with the Volume Label
+//! and Volume GUID.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{
+    allocation_bitmap,
+    block_device::BlockDevice,
+    cluster_heap::ClusterHeap,
+    dir_entry::{
+        AllocationBitmapEntry, DirEntry, UpcaseTableEntry, VolumeGuidEntry,
+        VolumeLabelEntry,
+    },
+    error::Error,
+    fat::Fat,
+    upcase_table,
+};
+
+/// The critical primary entries of a root directory, and the Volume Label
+/// and Volume GUID if it has them.
+pub struct RootEntries {
+    pub allocation_bitmaps: Vec<AllocationBitmapEntry>,
+    pub upcase_table: UpcaseTableEntry,
+    pub volume_label: Option<VolumeLabelEntry>,
+    pub volume_guid: Option<VolumeGuidEntry>,
+}
+
+impl RootEntries {
+    /// Writes the entries as the only ones of a root directory consisting of
+    /// the single cluster `root`.
+    pub fn write(
+        &self,
+        heap: ClusterHeap,
+        fat: &Fat,
+        disk: &mut impl BlockDevice,
+        root: u32,
+    ) -> Result<(), Error> {
+        let mut entries: Vec<DirEntry> = self
+            .allocation_bitmaps
+            .iter()
+            .map(|bitmap| DirEntry::AllocationBitmap(*bitmap))
+            .collect();
+        entries.push(DirEntry::UpcaseTable(self.upcase_table));
+        entries.extend(self.volume_label.map(DirEntry::VolumeLabel));
+        entries.extend(self.volume_guid.map(DirEntry::VolumeGuid));
+        let mut directory = vec![0u8; heap.bytes_per_cluster() as usize];
+        for (entry, slot) in
+            entries.iter().zip(directory.chunks_mut(DirEntry::SIZE))
+        {
+            slot.copy_from_slice(&entry.to_bytes()?);
+        }
+        disk.write_at(heap.cluster_offset(root), &directory)?;
+        fat.set_chain(disk, [root])?;
+        disk.flush()
+    }
+
+    /// Finds the entries in the root directory starting at cluster `root`.
+    pub fn read(
+        heap: ClusterHeap,
+        fat: &Fat,
+        disk: &mut impl BlockDevice,
+        root: u32,
+    ) -> Result<Self, Error> {
+        let clusters: Vec<u32> =
+            fat.chain(disk, root).collect::<Result<_, _>>()?;
+        let mut allocation_bitmaps = Vec::new();
+        let mut upcase_table = None;
+        let mut volume_label = None;
+        let mut volume_guid = None;
+        let mut directory = vec![0u8; heap.bytes_per_cluster() as usize];
+        'clusters: for cluster in clusters {
+            disk.read_at(heap.cluster_offset(cluster), &mut directory)?;
+            for slot in directory.chunks(DirEntry::SIZE) {
+                let entry = DirEntry::parse(slot)?;
+                let in_use = entry.entry_type().in_use();
+                match entry {
+                    DirEntry::EndOfDirectory => break 'clusters,
+                    // deleted entries
+                    _ if !in_use => {}
+                    DirEntry::AllocationBitmap(entry) => {
+                        allocation_bitmaps.push(entry)
+                    }
+                    DirEntry::UpcaseTable(entry) => upcase_table = Some(entry),
+                    DirEntry::VolumeLabel(entry) => volume_label = Some(entry),
+                    DirEntry::VolumeGuid(entry) => volume_guid = Some(entry),
+                    _ => {}
+                }
+            }
+        }
+        if allocation_bitmaps.is_empty() {
+            return Err(allocation_bitmap::Error::Missing.into());
+        }
+        Ok(Self {
+            allocation_bitmaps,
+            upcase_table: upcase_table.ok_or(upcase_table::Error::Missing)?,
+            volume_label,
+            volume_guid,
+        })
+    }
+
+    /// The Allocation Bitmap entry for the active FAT.
+    pub fn allocation_bitmap(
+        &self,
+        fat: &Fat,
+    ) -> Result<&AllocationBitmapEntry, Error> {
+        self.allocation_bitmaps
+            .iter()
+            .find(|entry| entry.bitmap_identifier() == fat.active())
+            .ok_or(allocation_bitmap::Error::Missing.into())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::io::Cursor;
+
+    use uguid::guid;
+
+    use crate::{
+        block_device::IoDevice,
+        fat_entry::Link,
+        format_options::FormatOptions,
+        shift::ShiftedSectors,
+        test_util::{format, format_with, DISK_SIZE},
+        time::DateTime,
+        upcase_table::UpcaseTable,
+        FileSystem,
+    };
+
+    #[test]
+    pub fn format_and_mount() {
+        let guid = guid!("01234567-89ab-cdef-0123-456789abcdef");
+        let options =
+            FormatOptions::reproducible(DateTime::default(), 1234, guid);
+        let fs = format_with(
+            vec![0xAA; DISK_SIZE],
+            ShiftedSectors::from(3),
+            &options,
+        );
+        let mut fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.volume_guid(), Some(guid));
+        assert_eq!(*fs.upcase_table(), UpcaseTable::generate());
+
+        // the bitmap and the up-case table take up one 4KB cluster each
+        let root = fs
+            .boot_region()
+            .boot_sector()
+            .first_cluster_of_root_directory();
+        assert_eq!(root, 4);
+        for cluster in 2..=root {
+            assert!(fs.allocation_bitmap().is_allocated(cluster).unwrap());
+        }
+        assert_eq!(
+            fs.free_cluster_count(),
+            fs.fat().cluster_count() - (root - 1)
+        );
+        let fat = *fs.fat();
+        let disk = fs.boot_region_mut().disk_mut();
+        assert_eq!(fat.link(disk, 2).unwrap(), Link::EndOfChain);
+        assert_eq!(fat.link(disk, 3).unwrap(), Link::EndOfChain);
+        assert_eq!(fat.link(disk, root).unwrap(), Link::EndOfChain);
+        assert_eq!(fat.link(disk, root + 1).unwrap(), Link::Free);
+
+        let fs = format();
+        let fs = FileSystem::mount(fs.unmount()).unwrap();
+        assert_eq!(fs.volume_guid(), None);
+    }
+
+    #[test]
+    pub fn ignore_entries_after_end() {
+        let fs = format();
+        let root = fs
+            .boot_region()
+            .boot_sector()
+            .first_cluster_of_root_directory();
+        let offset = fs.cluster_heap().cluster_offset(root) as usize;
+        let mut image = fs.unmount().into_inner().into_inner();
+        // the bitmap and up-case table entries are followed by the end of
+        // the directory
+        let upcase = offset + 32;
+        assert_eq!(image[upcase], 0x82);
+        assert_eq!(image[upcase + 32], 0x00);
+        // a stale, in use up-case table entry with the wrong checksum
+        image.copy_within(upcase..upcase + 32, upcase + 64);
+        image[upcase + 64 + 4] ^= 0xFF;
+
+        let disk = IoDevice::new(Cursor::new(image)).unwrap();
+        let fs = FileSystem::mount(disk).unwrap();
+        assert_eq!(*fs.upcase_table(), UpcaseTable::generate());
+    }
+}
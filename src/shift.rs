@@ -20,6 +20,14 @@ impl Shifted {
 }
 
 impl Unshifted {
+    pub const fn try_shift(&self) -> Option<Shifted> {
+        if self.0.is_power_of_two() {
+            Some(Shifted(self.0.ilog2() as u8))
+        } else {
+            None
+        }
+    }
+
     pub const fn shift(&self) -> Shifted {
         assert!(self.0.is_power_of_two());
         let shifted = self.0.ilog2();
@@ -79,6 +87,12 @@ impl BytesPerSector {
         ShiftedBytes(self.0.shift())
     }
 
+    /// Like [`BytesPerSector::shift`], except that `None` is returned rather
+    /// than panicking when the number of bytes is not a power of two.
+    pub fn try_shift(&self) -> Option<ShiftedBytes> {
+        self.0.try_shift().map(ShiftedBytes)
+    }
+
     pub const fn new(bytes: usize) -> Self {
         Self(Unshifted(bytes))
     }
@@ -88,6 +102,13 @@ impl SectorsPerCluster {
     pub fn shift(&self) -> ShiftedSectors {
         ShiftedSectors(self.0.shift())
     }
+
+    /// Like [`SectorsPerCluster::shift`], except that `None` is returned
+    /// rather than panicking when the number of sectors is not a power of
+    /// two.
+    pub fn try_shift(&self) -> Option<ShiftedSectors> {
+        self.0.try_shift().map(ShiftedSectors)
+    }
 }
 
 impl From<ShiftedBytes> for BytesPerSector {
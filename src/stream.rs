@@ -0,0 +1,166 @@
+//! # Purpose
+//! Locates the clusters holding the data of a file or directory. They either
+//! form a chain in the FAT or, when the NoFatChain flag is set, are
+//! contiguous and their FAT entries are not used.
+
+use core::{fmt::Display, ops::Range};
+
+use alloc::vec::Vec;
+
+use crate::{
+    block_device::BlockDevice, cluster_heap::ClusterHeap,
+    dir_entry::StreamExtensionEntry, fat, fat::Fat,
+};
+
+/// Ways an allocation can be at odds with the length of its data.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The allocation has fewer clusters than `data_length` bytes need.
+    TooShort { data_length: u64, clusters: usize },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::TooShort {
+                data_length,
+                clusters,
+            } => write!(
+                f,
+                "{} clusters cannot hold {} bytes of data.",
+                clusters, data_length
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// The allocation of a file or directory in the Cluster Heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stream {
+    /// The first cluster of the allocation, or 0 when there is none.
+    pub first_cluster: u32,
+    /// The length of the allocation in bytes. Only used to count the
+    /// clusters of contiguous allocations, as FAT chains end on their own.
+    pub data_length: u64,
+    pub no_fat_chain: bool,
+}
+
+impl Stream {
+    /// The root directory, which always has a FAT chain.
+    pub fn root(first_cluster: u32) -> Self {
+        Self {
+            first_cluster,
+            data_length: 0,
+            no_fat_chain: false,
+        }
+    }
+
+    /// Locates the clusters of the allocation, following its FAT chain
+    /// unless it is contiguous.
+    pub fn locate(
+        &self,
+        heap: ClusterHeap,
+        fat: &Fat,
+        disk: &mut impl BlockDevice,
+    ) -> Result<Clusters, crate::Error> {
+        if self.first_cluster == 0 {
+            return Ok(Clusters::Chain(Vec::new()));
+        }
+        if !self.no_fat_chain {
+            return Ok(Clusters::Chain(
+                fat.chain(disk, self.first_cluster)
+                    .collect::<Result<_, _>>()?,
+            ));
+        }
+        let count = heap.clusters_for(self.data_length);
+        if count == 0 {
+            return Ok(Clusters::Contiguous(
+                self.first_cluster..self.first_cluster,
+            ));
+        }
+        let end = self.first_cluster as u64 + count;
+        for cluster in [self.first_cluster as u64, end.saturating_sub(1)] {
+            let cluster = u32::try_from(cluster).unwrap_or(u32::MAX);
+            if !heap.contains(cluster) {
+                return Err(fat::Error::OutOfRange {
+                    cluster,
+                    cluster_count: heap.cluster_count(),
+                }
+                .into());
+            }
+        }
+        Ok(Clusters::Contiguous(self.first_cluster..end as u32))
+    }
+
+    /// Lists the clusters of the allocation in order.
+    pub fn clusters(
+        &self,
+        heap: ClusterHeap,
+        fat: &Fat,
+        disk: &mut impl BlockDevice,
+    ) -> Result<Vec<u32>, crate::Error> {
+        Ok(match self.locate(heap, fat, disk)? {
+            Clusters::Chain(chain) => chain,
+            Clusters::Contiguous(range) => range.collect(),
+        })
+    }
+}
+
+/// The clusters of an allocation, which are only listed one by one when they
+/// form a FAT chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Clusters {
+    Chain(Vec<u32>),
+    Contiguous(Range<u32>),
+}
+
+impl Clusters {
+    pub fn len(&self) -> usize {
+        match self {
+            Clusters::Chain(chain) => chain.len(),
+            Clusters::Contiguous(range) => range.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cluster at `index` within the allocation.
+    pub fn get(&self, index: usize) -> Option<u32> {
+        match self {
+            Clusters::Chain(chain) => chain.get(index).copied(),
+            Clusters::Contiguous(range) => range.clone().nth(index),
+        }
+    }
+
+    /// The number of clusters from `index` on which follow each other on the
+    /// volume, so that they can be transferred at once.
+    pub fn run(&self, index: usize) -> usize {
+        match self {
+            Clusters::Chain(chain) => {
+                let Some(first) = chain.get(index) else {
+                    return 0;
+                };
+                chain[index..]
+                    .iter()
+                    .zip(*first..)
+                    .take_while(|(cluster, expected)| **cluster == *expected)
+                    .count()
+            }
+            Clusters::Contiguous(range) => range.len().saturating_sub(index),
+        }
+    }
+}
+
+impl From<&StreamExtensionEntry> for Stream {
+    fn from(entry: &StreamExtensionEntry) -> Self {
+        Self {
+            first_cluster: entry.first_cluster,
+            data_length: entry.data_length,
+            no_fat_chain: entry.general_secondary_flags.no_fat_chain(),
+        }
+    }
+}
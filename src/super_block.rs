@@ -1,26 +1,30 @@
 pub mod boot_code;
-mod bound_error;
+pub mod bound_error;
 pub mod extended_boot_code;
-mod extended_boot_sector;
+pub mod extended_boot_sector;
+pub mod geometry;
 mod must_be_zero;
-mod volume_flags;
+pub mod volume_flags;
 
 use core::cmp;
-use core::u64;
 
-use crate::fat_entry::FatEntry;
+use crate::error::Error;
+use crate::format_options::FormatOptions;
 use crate::shift::{
     BytesPerSector, SectorsPerCluster, ShiftedBytes, ShiftedSectors,
 };
+use crate::time::TimeSource;
 
-use self::bound_error::BoundError;
+pub use self::bound_error::BoundError;
 use self::{
-    boot_code::BootCode, must_be_zero::MustBeZero, volume_flags::VolumeFlags,
+    boot_code::BootCode, geometry::Geometry, must_be_zero::MustBeZero,
+    volume_flags::VolumeFlags,
 };
 use macros::DiskLayout;
-use rand::{rngs::OsRng, Rng};
 use serde::{Deserialize, Serialize};
 
+const FILE_SYSTEM_NAME: &[u8; 8] = b"EXFAT   ";
+
 // use macros::DiskLayout;
 #[derive(DiskLayout, Default, Serialize, Deserialize, Clone)]
 pub struct SuperBlock {
@@ -72,12 +76,10 @@ pub struct SuperBlock {
     ///
     /// - At most 2**64- 1, the largest value this field can describe.
     ///
-    ///   However, if the size of the Excess
-    /// Space sub-region is 0, then the
-    ///   largest value of this field is
-    /// `ClusterHeapOffset + (232 - 11) *
-    /// 2**SectorsPerClusterShift`.
-    #[min(2u64.pow(20) / (*self.bytes_per_sector() as u64))]
+    ///   However, if the size of the Excess Space sub-region is 0, then the
+    ///   largest value of this field is `ClusterHeapOffset + (2**32 - 11) *
+    ///   2**SectorsPerClusterShift`.
+    #[min(1u64 << 20u8.saturating_sub(self.bytes_per_sector_shift.inner()))]
     #[max(u64::MAX)]
     volume_length: u64,
     /// ## Description
@@ -89,19 +91,17 @@ pub struct SuperBlock {
     /// storage media. ## Value
     /// The valid range of values for this field
     /// shall be:
+    ///
     /// - At least 24, which accounts for the sectors the Main Boot and Backup
-    /// Boot regions consume
-
-    /// - At most `ClusterHeapOffset - (FatLength
-    ///   * NumberOfFats)`, which accounts
-    /// for the sectors the Cluster Heap consumes
+    ///   Boot regions consume
+    ///
+    /// - At most `ClusterHeapOffset - (FatLength * NumberOfFats)`, which
+    ///   accounts for the sectors the Cluster Heap consumes
     #[min(24u32)]
-    #[max((
+    #[max(u32::try_from(
         self.cluster_heap_offset as i64
-        - (
-            self.fat_length * self.number_of_fats as u32
-        ) as i64
-    ) as u32)]
+            - self.fat_length as i64 * self.number_of_fats as i64
+    ).unwrap_or(0))]
     fat_offset: u32,
     /// ## Description
     /// The FatLength field shall describe the
@@ -117,15 +117,13 @@ pub struct SuperBlock {
     /// - At most `(ClusterHeapOffset - FatOffset) / NumberOfFats` rounded down
     ///   to the nearest integer, which ensures the FATs exist before the
     ///   Cluster Heap
-    #[min(
-        (
-            ((self.cluster_count as u64 + 2) * 4)
-            / *self.bytes_per_sector() as u64
-        ) as u32
-    )]
+    #[min(u32::try_from(
+        ((self.cluster_count as u64 + 2) * 4)
+            .div_ceil(saturating_pow2(self.bytes_per_sector_shift.inner()))
+    ).unwrap_or(u32::MAX))]
     #[max(
-        (self.cluster_heap_offset - self.fat_offset) as u32
-        / self.number_of_fats as u32
+        self.cluster_heap_offset.saturating_sub(self.fat_offset)
+            / (self.number_of_fats as u32).max(1)
     )]
     fat_length: u32,
     /// ## Description
@@ -143,15 +141,17 @@ pub struct SuperBlock {
     ///
     /// - At most `2**32 - 1` or `VolumeLength - (ClusterCount *
     ///   2**SectorsPerClusterShift)`, whichever calculation is less
-    #[min(self.fat_offset + self.fat_length * self.number_of_fats as u32)]
-    #[max(
-        cmp::min(
-            (u32::MAX - 1) as i128,
-            self.volume_length as i128 - (
-                self.cluster_count as i128
-                * *self.sectors_per_cluster() as i128
-            )).try_into().or::<u32>(Ok(0)).unwrap()
-        )]
+    #[min(u32::try_from(
+        self.fat_offset as u64
+            + self.fat_length as u64 * self.number_of_fats as u64
+    ).unwrap_or(u32::MAX))]
+    #[max(u32::try_from(
+        self.volume_length.saturating_sub(
+            (self.cluster_count as u64).saturating_mul(saturating_pow2(
+                self.sectors_per_cluster_shift.inner()
+            ))
+        )
+    ).unwrap_or(u32::MAX))]
     cluster_heap_offset: u32,
     /// ## Description
     ///
@@ -171,10 +171,8 @@ pub struct SuperBlock {
     ///
     /// - `2**32- 11`, which is the maximum number of clusters a FAT can
     ///   describe
-    #[min(cmp::min(u32::MAX - 11, (self.volume_length as u32 - self.cluster_heap_offset) / 
-    *self.sectors_per_cluster() as u32))]
-    #[max(cmp::min(u32::MAX - 11, (self.volume_length as u32 - self.cluster_heap_offset) / 
-    *self.sectors_per_cluster() as u32))]
+    #[min(self.max_cluster_count())]
+    #[max(self.max_cluster_count())]
     cluster_count: u32,
     /// ## Description
     /// The FirstClusterOfRootDirectory field
@@ -195,7 +193,7 @@ pub struct SuperBlock {
     /// - At most ClusterCount + 1, the index of the last cluster in the
     ///   Cluster Heap
     #[min(2u32)]
-    #[max(self.cluster_count + 1u32)]
+    #[max(self.cluster_count.saturating_add(1))]
     first_cluster_of_root_directory: u32,
     /// ## Description
     /// The VolumeSerialNumber field shall
@@ -277,7 +275,7 @@ pub struct SuperBlock {
     /// - At most 25 - BytesPerSectorShift, which evaluates to a cluster size
     ///   of 32MB
     #[min(0u8)]
-    #[max(25u8 - self.bytes_per_sector_shift.inner())]
+    #[max(25u8.saturating_sub(self.bytes_per_sector_shift.inner()))]
     sectors_per_cluster_shift: ShiftedSectors,
     /// The NumberOfFats field shall describe the
     /// number of FATs and Allocation Bitmaps
@@ -351,6 +349,12 @@ pub struct SuperBlock {
     excess_space: (),
 }
 
+/// `2**shift`, saturating instead of overflowing so that bounds can be
+/// checked on corrupt boot sectors without panicking.
+fn saturating_pow2(shift: u8) -> u64 {
+    1u64.checked_shl(shift as u32).unwrap_or(u64::MAX)
+}
+
 pub fn round_up(lhs: impl Into<u64>, step: impl Into<u64>) -> u64 {
     let lhs: u64 = lhs.into();
     let step: u64 = step.into();
@@ -359,61 +363,89 @@ pub fn round_up(lhs: impl Into<u64>, step: impl Into<u64>) -> u64 {
 }
 
 impl SuperBlock {
-    /// hardware_division_interval: set to a power
-    /// of two based on where the
-    /// hardware prefers divisions.
+    /// The boot sector of a new volume of `volume_length` bytes, laid out by
+    /// [`Geometry::plan`] with the FAT count and alignment of `options`.
     pub fn new(
         bytes_per_sector: BytesPerSector,
         sectors_per_cluster: SectorsPerCluster,
         boot_code: BootCode,
         volume_length: u64,
-    ) -> Self {
-        let mut out = Self::default();
-        out.jump_boot = [0xEB, 0x76, 0x90];
-        out.file_system_name = *b"EXFAT   ";
-        let bytes_shifted = bytes_per_sector.shift();
-        let sectors_shifted = sectors_per_cluster.shift();
-        if bytes_shifted.inner() + sectors_shifted.inner() > 25 {
-            panic!("A cluster cannot be bigger than 32mb");
+        options: &FormatOptions<impl TimeSource>,
+    ) -> Result<Self, Error> {
+        let geometry = Geometry::plan(
+            volume_length,
+            bytes_per_sector,
+            sectors_per_cluster,
+            options.number_of_fats,
+            options.alignment.unwrap_or(*sectors_per_cluster as u32),
+        )?;
+        let mut out = Self::from_geometry(&geometry);
+        out.boot_code = boot_code;
+        out.volume_serial_number = options.volume_serial_number();
+        out.file_system_revision = [0, 1];
+        out.drive_select = 0x80;
+        out.percent_in_use = 0;
+
+        out.verify_bounds()?;
+        Ok(out)
+    }
+
+    /// A boot sector laid out as `geometry` describes, with every other field
+    /// left at its default.
+    fn from_geometry(geometry: &Geometry) -> Self {
+        Self {
+            jump_boot: [0xEB, 0x76, 0x90],
+            file_system_name: *FILE_SYSTEM_NAME,
+            volume_length: geometry.volume_length,
+            fat_offset: geometry.fat_offset,
+            fat_length: geometry.fat_length,
+            cluster_heap_offset: geometry.cluster_heap_offset,
+            cluster_count: geometry.cluster_count,
+            // placed after the Allocation Bitmap and Up-case Table once
+            // FileSystem::format has written them
+            first_cluster_of_root_directory: 2,
+            bytes_per_sector_shift: geometry.bytes_per_sector_shift,
+            sectors_per_cluster_shift: geometry.sectors_per_cluster_shift,
+            number_of_fats: geometry.number_of_fats,
+            ..Default::default()
         }
+    }
 
-        out.boot_code = boot_code;
+    /// Whether the sector carries the AA55h BootSignature.
+    pub fn has_valid_signature(&self) -> bool {
+        self.boot_code.has_valid_signature()
+    }
+
+    /// Whether the FileSystemName field reads "EXFAT   ".
+    pub fn is_exfat(&self) -> bool {
+        &self.file_system_name == FILE_SYSTEM_NAME
+    }
 
-        out.bytes_per_sector_shift = bytes_per_sector.shift();
-        out.sectors_per_cluster_shift = sectors_per_cluster.shift();
-        out.fat_offset = 24;
-        out.number_of_fats = 2;
+    /// The valid value of the ClusterCount field given the VolumeLength,
+    /// ClusterHeapOffset and SectorsPerClusterShift fields.
+    fn max_cluster_count(&self) -> u32 {
+        cmp::min(
+            geometry::MAX_CLUSTER_COUNT as u64,
+            self.volume_length
+                .saturating_sub(self.cluster_heap_offset as u64)
+                / saturating_pow2(self.sectors_per_cluster_shift.inner()),
+        ) as u32
+    }
 
-        let cluster_size: u64 = (*bytes_per_sector * *sectors_per_cluster) as u64;
+    pub fn volume_flags(&self) -> &VolumeFlags {
+        &self.volume_flags
+    }
 
-        let clusters_max = volume_length / cluster_size;
-        let fat_sector_count = (clusters_max * size_of::<FatEntry>() as u64)
-            .div_ceil(*out.bytes_per_sector() as u64);
-        out.partition_offset = 0; // first sector
-        out.volume_length = volume_length / (*out.bytes_per_sector() as u64);
-        out.fat_offset = 24;
-        out.fat_length = round_up(
-            out.fat_offset as u64 + fat_sector_count,
-            *out.sectors_per_cluster() as u64 - out.fat_offset as u64,
-        ) as u32;
-        out.cluster_heap_offset =
-            (cluster_size * 2 / *out.bytes_per_sector() as u64) as u32;
-        out.cluster_count = (out.volume_length as u32 - out.cluster_heap_offset) / 
-        *out.sectors_per_cluster() as u32;
+    pub fn set_volume_flags(&mut self, volume_flags: VolumeFlags) {
+        self.volume_flags = volume_flags;
+    }
 
-        out.first_cluster_of_root_directory = 2;
-        out.volume_serial_number = OsRng.gen(); // HACK should instead use
-                                                // current date and time.
-        out.file_system_revision = [0, 1];
-        out.drive_select = 0x80;
-        out.percent_in_use = 0;
+    pub fn percent_in_use(&self) -> u8 {
+        self.percent_in_use
+    }
 
-        out.boot_code = BootCode::default();
-        if let Err(e) = out.verify_bounds() {
-            panic!("{}", e)
-            // handle the error properly here
-        }
-        out
+    pub fn set_percent_in_use(&mut self, percent_in_use: u8) {
+        self.percent_in_use = percent_in_use;
     }
 
     pub fn bytes_per_sector(&self) -> BytesPerSector {
@@ -422,27 +454,125 @@ impl SuperBlock {
     pub fn sectors_per_cluster(&self) -> SectorsPerCluster {
         self.sectors_per_cluster_shift.unshift()
     }
-    pub(crate) fn set_boot_code(&mut self, boot_code: BootCode) {
+    pub fn volume_serial_number(&self) -> u32 {
+        self.volume_serial_number
+    }
+    /// The size of the volume in sectors.
+    pub fn volume_length(&self) -> u64 {
+        self.volume_length
+    }
+    /// The sector the First FAT starts at.
+    pub fn fat_offset(&self) -> u32 {
+        self.fat_offset
+    }
+    /// The length of each FAT in sectors.
+    pub fn fat_length(&self) -> u32 {
+        self.fat_length
+    }
+    pub fn number_of_fats(&self) -> u8 {
+        self.number_of_fats
+    }
+    /// The sector the Cluster Heap starts at.
+    pub fn cluster_heap_offset(&self) -> u32 {
+        self.cluster_heap_offset
+    }
+    pub fn cluster_count(&self) -> u32 {
+        self.cluster_count
+    }
+    pub fn first_cluster_of_root_directory(&self) -> u32 {
+        self.first_cluster_of_root_directory
+    }
+    pub fn set_first_cluster_of_root_directory(&mut self, cluster: u32) {
+        self.first_cluster_of_root_directory = cluster;
+    }
+
+    pub fn set_boot_code(&mut self, boot_code: BootCode) {
         self.boot_code = boot_code;
     }
 }
 #[cfg(test)]
 mod tests {
-    use bincode::Options;
-
-    use crate::shift::{ShiftedBytes, ShiftedSectors};
+    use crate::{
+        error::Error,
+        format_options::FormatOptions,
+        shift::{BytesPerSector, ShiftedBytes, ShiftedSectors},
+    };
 
     use super::{boot_code::BootCode, SuperBlock};
 
     #[test]
     pub fn serialize() {
         const V_SIZE: u64 = 2u64.pow(35);
-        let bs = SuperBlock::new(ShiftedBytes::new(12).into(), ShiftedSectors::from(8).into(), BootCode::default(), V_SIZE);
-        let my_options = bincode::DefaultOptions::new()
-            .allow_trailing_bytes()
-            .with_fixint_encoding()
-            .with_little_endian();
-        let serialized = my_options.serialize(&bs).unwrap();
-        println!("{serialized:?}");
+        let bs = SuperBlock::new(
+            ShiftedBytes::new(12).into(),
+            ShiftedSectors::from(8).into(),
+            BootCode::default(),
+            V_SIZE,
+            &FormatOptions::default(),
+        )
+        .unwrap();
+        let serialized =
+            bincode::serde::encode_to_vec(&bs, bincode::config::legacy())
+                .unwrap();
+        assert_eq!(&serialized[3..11], b"EXFAT   ");
+        assert_eq!(&serialized[510..512], &[0x55, 0xAA]);
+    }
+
+    #[test]
+    pub fn new_rejects_bad_input() {
+        const V_SIZE: u64 = 2u64.pow(35);
+        assert!(matches!(
+            SuperBlock::new(
+                BytesPerSector::new(1000),
+                ShiftedSectors::from(0).into(),
+                BootCode::default(),
+                V_SIZE,
+                &FormatOptions::default(),
+            ),
+            Err(Error::NotPowerOfTwo { .. })
+        ));
+        // a 64MB cluster
+        assert!(matches!(
+            SuperBlock::new(
+                ShiftedBytes::new(12).into(),
+                ShiftedSectors::from(14).into(),
+                BootCode::default(),
+                V_SIZE,
+                &FormatOptions::default(),
+            ),
+            Err(Error::Bound(_))
+        ));
+        // too small to hold a 1MB volume
+        assert!(matches!(
+            SuperBlock::new(
+                ShiftedBytes::new(9).into(),
+                ShiftedSectors::from(0).into(),
+                BootCode::default(),
+                2u64.pow(10),
+                &FormatOptions::default(),
+            ),
+            Err(Error::Bound(_))
+        ));
+        assert!(matches!(
+            BootCode::new(&[0; 391]),
+            Err(Error::BootCodeTooLong { .. })
+        ));
+    }
+
+    #[test]
+    pub fn serial_number_from_options() {
+        let options = FormatOptions {
+            volume_serial_number: Some(0x1234_5678),
+            ..Default::default()
+        };
+        let bs = SuperBlock::new(
+            ShiftedBytes::new(12).into(),
+            ShiftedSectors::from(8).into(),
+            BootCode::default(),
+            2u64.pow(35),
+            &options,
+        )
+        .unwrap();
+        assert_eq!(bs.volume_serial_number(), 0x1234_5678);
     }
 }
@@ -1,5 +1,6 @@
-use macros::DiskLayout;
 use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
 use serde_big_array::BigArray;
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
@@ -25,20 +26,30 @@ pub struct BootCode(
     u16,
 );
 
+const VALID_SIGNATURE: u16 = 0xAA55;
+
 impl BootCode {
-    pub fn new(boot_code: &[u8]) -> Self {
+    pub fn new(boot_code: &[u8]) -> Result<Self, Error> {
+        let mut out = Self::default();
         // check length is not longer than 390
-        if boot_code.len() > 390 {
-            panic!("Boot code is too long!");
+        if boot_code.len() > out.0.len() {
+            return Err(Error::BootCodeTooLong {
+                length: boot_code.len(),
+                max: out.0.len(),
+            });
         }
-        let mut out = Self::default();
         out.0[0..boot_code.len()].copy_from_slice(boot_code);
-        out
+        Ok(out)
+    }
+
+    /// Whether the sector carries the AA55h BootSignature.
+    pub fn has_valid_signature(&self) -> bool {
+        self.1 == VALID_SIGNATURE
     }
 }
 
 impl Default for BootCode {
     fn default() -> Self {
-        Self([0xf4; 390], 0xAA55)
+        Self([0xf4; 390], VALID_SIGNATURE)
     }
 }
@@ -1,7 +1,7 @@
 extern crate alloc;
 use core::fmt::Display;
 
-use alloc::{format, string::String};
+use alloc::string::String;
 
 #[derive(Debug)]
 pub enum BoundError {
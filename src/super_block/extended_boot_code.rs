@@ -5,7 +5,7 @@ mod de;
 mod ser;
 pub use de::BytesPerSectorSeed;
 
-use crate::shift::BytesPerSector;
+use crate::{error::Error, shift::BytesPerSector, super_block::BoundError};
 
 const VALID_SIGNATURE: u32 = 0xAA550000;
 
@@ -30,27 +30,41 @@ pub struct ExtendedBootCode {
 }
 
 impl ExtendedBootCode {
-    pub fn new(boot_code: &[u8], sector_size: BytesPerSector) -> Self {
+    pub fn new(
+        boot_code: &[u8],
+        sector_size: BytesPerSector,
+    ) -> Result<Self, Error> {
+        // There must be at least 4 bytes per sector to fit the signature.
         if *sector_size < 4 {
-            panic!("There must be at least 4 bytes per sector to fit the signature.")
+            return Err(BoundError::too_small(
+                "sector_size",
+                *sector_size as u64,
+                4u64,
+            )
+            .into());
         }
-        // assert length of boot_code is not too long
+        // check length of boot_code is not too long
         if boot_code.len() > (*sector_size - 4) {
-            panic!("Boot code is too long!");
+            return Err(Error::BootCodeTooLong {
+                length: boot_code.len(),
+                max: *sector_size - 4,
+            });
         }
         let mut bc = vec![0x00; *sector_size - 4];
         bc[0..boot_code.len()].copy_from_slice(boot_code);
-        Self {
+        Ok(Self {
             code: bc,
             signature: VALID_SIGNATURE,
-        }
+        })
+    }
+
+    /// Whether the sector carries the AA550000h ExtendedBootSignature.
+    pub fn has_valid_signature(&self) -> bool {
+        self.signature == VALID_SIGNATURE
     }
 }
 #[cfg(test)]
 mod tests {
-
-    use bincode::Options;
-
     use crate::{
         shift::ShiftedBytes,
         super_block::extended_boot_code::BytesPerSectorSeed,
@@ -61,19 +75,15 @@ mod tests {
     #[test]
     fn serde() {
         const BYTES_PER_SECTOR: BytesPerSector = ShiftedBytes::new(3).unshift();
-        let ebs = ExtendedBootCode::new(b"code", BYTES_PER_SECTOR);
-        let out: Vec<u8> = Vec::with_capacity(*BYTES_PER_SECTOR);
-        let mut cursor = std::io::Cursor::new(out);
-
-        let bincode_options = bincode::DefaultOptions::new()
-            .allow_trailing_bytes()
-            .with_little_endian()
-            .with_fixint_encoding();
-        bincode_options.serialize_into(&mut cursor, &ebs).unwrap();
-        cursor.set_position(0);
-        let deserialized = bincode_options
-            .deserialize_from_seed(BytesPerSectorSeed(BYTES_PER_SECTOR), cursor)
-            .unwrap();
+        let ebs = ExtendedBootCode::new(b"code", BYTES_PER_SECTOR).unwrap();
+        let bincode_options = bincode::config::legacy();
+        let out = bincode::serde::encode_to_vec(&ebs, bincode_options).unwrap();
+        let (deserialized, _) = bincode::serde::seed_decode_from_slice(
+            BytesPerSectorSeed(BYTES_PER_SECTOR),
+            &out,
+            bincode_options,
+        )
+        .unwrap();
 
         assert!(deserialized == ebs);
     }
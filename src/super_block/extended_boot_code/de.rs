@@ -1,10 +1,11 @@
-use std::ops::Deref;
+use alloc::vec;
+use core::ops::Deref;
 
 use serde::de::{DeserializeSeed, SeqAccess};
 
 use crate::shift::BytesPerSector;
 
-use super::{ExtendedBootCode, VALID_SIGNATURE};
+use super::ExtendedBootCode;
 
 pub struct BytesPerSectorSeed(pub BytesPerSector);
 
@@ -12,7 +13,7 @@ impl Deref for BytesPerSectorSeed {
     type Target = usize;
 
     fn deref(&self) -> &Self::Target {
-        &(*self.0)
+        &self.0
     }
 }
 
@@ -59,16 +60,12 @@ impl<'de> DeserializeSeed<'de> for BytesPerSectorSeed {
                         ),
                     )?;
                 }
+                // the signature is checked by whoever loads the sector so
+                // that a corrupt sector can be reported as such rather than
+                // as a deserialization failure.
                 let signature: u32 = seq
                     .next_element()?
                     .ok_or(serde::de::Error::missing_field("signature"))?;
-                // check that signature is valid
-                if signature != VALID_SIGNATURE {
-                    return Err(serde::de::Error::custom(
-                        "Signature is invalid. \
-                        This boot code is corrupted.",
-                    ));
-                }
                 Ok(ExtendedBootCode {
                     code: boot_code,
                     signature,
@@ -1,42 +1,33 @@
-mod index;
+pub mod index;
 extern crate alloc;
-use std::io::{Read, Seek, Write};
 
 use self::index::Index;
-use bincode::{
-    config::{
-        AllowTrailing, FixintEncoding, LittleEndian, WithOtherEndian,
-        WithOtherIntEncoding, WithOtherTrailing,
-    },
-    DefaultOptions, Options,
-};
-use serde::{de::DeserializeSeed, Serialize};
 
-use crate::shift::BytesPerSector;
+use crate::{block_device::BlockDevice, error::Error, shift::BytesPerSector};
 
 use super::extended_boot_code::{BytesPerSectorSeed, ExtendedBootCode};
-pub trait Disk: Read + Write + Seek {}
-
-impl<T> Disk for T where T: Read + Write + Seek {}
 pub struct ExtendedBootSectors<'a, Disk>
 where
-    Disk: self::Disk,
+    Disk: BlockDevice,
 {
     disk: &'a mut Disk,
     sector_size: BytesPerSector,
 }
 impl<'a, Disk> ExtendedBootSectors<'a, Disk>
 where
-    Disk: self::Disk,
+    Disk: BlockDevice,
 {
     // only run on initial formatting
-    pub fn init(disk: &'a mut Disk, sector_size: BytesPerSector) -> Self {
+    pub fn init(
+        disk: &'a mut Disk,
+        sector_size: BytesPerSector,
+    ) -> Result<Self, Error> {
         let mut out = Self::open(disk, sector_size);
-        for index in (0..7).map(|i| Index::new(i).unwrap()) {
-            out.set_sector_code(index, &[]).unwrap();
+        for index in 0..8 {
+            out.set_sector_code(Index::new(index)?, &[])?;
         }
 
-        out
+        Ok(out)
     }
 
     pub fn open(disk: &'a mut Disk, sector_size: BytesPerSector) -> Self {
@@ -50,32 +41,35 @@ where
     pub fn load_sector(
         &mut self,
         index: Index,
-    ) -> Result<ExtendedBootCode, Box<bincode::ErrorKind>> {
+    ) -> Result<ExtendedBootCode, Error> {
+        let mut sector = alloc::vec![0u8; *self.sector_size];
         self.disk
-            .seek(std::io::SeekFrom::Start(self.offset_from_index(index)))?;
-        let my_options = bincode::DefaultOptions::new()
-            .allow_trailing_bytes()
-            .with_fixint_encoding()
-            .with_little_endian();
-        my_options.deserialize_from_seed(
+            .read_at(self.offset_from_index(index), &mut sector)?;
+        let (code, _) = bincode::serde::seed_decode_from_slice(
             BytesPerSectorSeed(self.sector_size),
-            &mut self.disk,
-        )
+            sector.as_slice(),
+            bincode::config::legacy(),
+        )?;
+        if !code.has_valid_signature() {
+            return Err(Error::InvalidSignature {
+                sector: *index as u64 + 1,
+            });
+        }
+        Ok(code)
     }
 
     pub fn set_sector_code(
         &mut self,
         index: Index,
         code: &[u8],
-    ) -> Result<(), Box<bincode::ErrorKind>> {
-        self.disk
-            .seek(std::io::SeekFrom::Start(self.offset_from_index(index)))?;
-        let bc = ExtendedBootCode::new(code, self.sector_size);
-        let my_options = bincode::DefaultOptions::new()
-            .allow_trailing_bytes()
-            .with_fixint_encoding()
-            .with_little_endian();
-        my_options.serialize_into(&mut self.disk, &bc)?;
-        Ok(())
+    ) -> Result<(), Error> {
+        let bc = ExtendedBootCode::new(code, self.sector_size)?;
+        let mut sector = alloc::vec![0u8; *self.sector_size];
+        bincode::serde::encode_into_slice(
+            &bc,
+            sector.as_mut_slice(),
+            bincode::config::legacy(),
+        )?;
+        self.disk.write_at(self.offset_from_index(index), &sector)
     }
 }
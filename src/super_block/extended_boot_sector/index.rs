@@ -1,5 +1,6 @@
 use core::{fmt::Display, ops::Deref};
 
+#[derive(Clone, Copy)]
 pub struct Index(u8);
 
 #[derive(Debug)]
@@ -46,3 +47,5 @@ impl Deref for Index {
         &self.0
     }
 }
+
+impl core::error::Error for Error {}
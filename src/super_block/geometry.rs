@@ -0,0 +1,312 @@
+//! # Purpose
+//! Plans where the FAT region and the Cluster Heap of a new volume go, so that
+//! the FatOffset, FatLength, ClusterHeapOffset and ClusterCount fields of the
+//! boot sector always satisfy their bounds.
+
+use crate::{
+    error::Error,
+    fat_entry::FatEntry,
+    shift::{BytesPerSector, SectorsPerCluster, ShiftedBytes, ShiftedSectors},
+};
+
+use super::{round_up, BoundError};
+
+/// The most clusters a FAT can describe, 2**32 - 11.
+pub const MAX_CLUSTER_COUNT: u32 = u32::MAX - 10;
+
+/// The sectors the Main Boot and Backup Boot regions consume.
+const BOOT_REGION_SECTORS: u64 = 24;
+
+/// The layout of a volume. Every length and offset is in sectors.
+#[derive(Clone, Copy)]
+pub struct Geometry {
+    pub bytes_per_sector_shift: ShiftedBytes,
+    pub sectors_per_cluster_shift: ShiftedSectors,
+    pub volume_length: u64,
+    pub number_of_fats: u8,
+    pub fat_offset: u32,
+    pub fat_length: u32,
+    pub cluster_heap_offset: u32,
+    pub cluster_count: u32,
+}
+
+impl Geometry {
+    /// Lays out a volume of `volume_length` bytes, any partial sector at the
+    /// end of which goes unused.
+    ///
+    /// Both the First FAT and the Cluster Heap start on a multiple of
+    /// `alignment` sectors, which lets them line up with the erase blocks of
+    /// flash media. The FAT is made as short as the Cluster Heap after it
+    /// allows.
+    pub fn plan(
+        volume_length: u64,
+        bytes_per_sector: BytesPerSector,
+        sectors_per_cluster: SectorsPerCluster,
+        number_of_fats: u8,
+        alignment: u32,
+    ) -> Result<Self, Error> {
+        let bytes_per_sector_shift =
+            bytes_per_sector.try_shift().ok_or(Error::NotPowerOfTwo {
+                field: "bytes_per_sector",
+                value: *bytes_per_sector,
+            })?;
+        let sectors_per_cluster_shift =
+            sectors_per_cluster
+                .try_shift()
+                .ok_or(Error::NotPowerOfTwo {
+                    field: "sectors_per_cluster",
+                    value: *sectors_per_cluster,
+                })?;
+        let shift = bytes_per_sector_shift.inner();
+        if shift < 9 {
+            return Err(BoundError::too_small(
+                "bytes_per_sector_shift",
+                shift,
+                9u8,
+            )
+            .into());
+        }
+        if shift > 12 {
+            return Err(BoundError::too_big(
+                "bytes_per_sector_shift",
+                shift,
+                12u8,
+            )
+            .into());
+        }
+        // A cluster cannot be bigger than 32MB
+        let max_sectors_per_cluster_shift = 25 - bytes_per_sector_shift.inner();
+        if sectors_per_cluster_shift.inner() > max_sectors_per_cluster_shift {
+            return Err(BoundError::too_big(
+                "sectors_per_cluster_shift",
+                sectors_per_cluster_shift.inner(),
+                max_sectors_per_cluster_shift,
+            )
+            .into());
+        }
+        if number_of_fats < 1 {
+            return Err(BoundError::too_small(
+                "number_of_fats",
+                number_of_fats,
+                1u8,
+            )
+            .into());
+        }
+        if number_of_fats > 2 {
+            return Err(BoundError::too_big(
+                "number_of_fats",
+                number_of_fats,
+                2u8,
+            )
+            .into());
+        }
+        if !alignment.is_power_of_two() {
+            return Err(Error::NotPowerOfTwo {
+                field: "alignment",
+                value: alignment as usize,
+            });
+        }
+
+        let bytes_per_sector = *bytes_per_sector as u64;
+        let sectors_per_cluster = *sectors_per_cluster as u64;
+        let volume_sectors = volume_length / bytes_per_sector;
+        // the smallest volume is 1MB
+        let min_sectors = (1 << 20) / bytes_per_sector;
+        if volume_sectors < min_sectors {
+            return Err(BoundError::too_small(
+                "volume_length",
+                volume_sectors,
+                min_sectors,
+            )
+            .into());
+        }
+
+        let alignment = alignment as u64;
+        let fat_offset = round_up(BOOT_REGION_SECTORS, alignment);
+        let heap_offset = |fat_length: u64| {
+            round_up(fat_offset + fat_length * number_of_fats as u64, alignment)
+        };
+        let cluster_count = |fat_length: u64| {
+            let clusters = volume_sectors
+                .saturating_sub(heap_offset(fat_length))
+                / sectors_per_cluster;
+            clusters.min(MAX_CLUSTER_COUNT as u64)
+        };
+        let fat_sectors = |clusters: u64| {
+            ((clusters + 2) * FatEntry::SIZE as u64).div_ceil(bytes_per_sector)
+        };
+        // A longer FAT leaves fewer clusters to describe, so the shortest FAT
+        // able to describe the clusters which remain is found by bisection.
+        // A FAT describing every cluster of an empty FAT region is always
+        // long enough.
+        let mut too_short = 0;
+        let mut long_enough = fat_sectors(cluster_count(0));
+        while long_enough - too_short > 1 {
+            let fat_length = too_short + (long_enough - too_short) / 2;
+            if fat_length >= fat_sectors(cluster_count(fat_length)) {
+                long_enough = fat_length;
+            } else {
+                too_short = fat_length;
+            }
+        }
+        let cluster_heap_offset = heap_offset(long_enough);
+        let cluster_count = cluster_count(long_enough);
+        // the clusters left may need less than the space set aside for the
+        // FAT, which is then left unused before the Cluster Heap.
+        let fat_length = fat_sectors(cluster_count);
+
+        if cluster_heap_offset > u32::MAX as u64 {
+            return Err(BoundError::too_big(
+                "cluster_heap_offset",
+                cluster_heap_offset,
+                u32::MAX,
+            )
+            .into());
+        }
+        if cluster_count == 0 {
+            return Err(BoundError::too_small(
+                "volume_length",
+                volume_sectors,
+                cluster_heap_offset + sectors_per_cluster,
+            )
+            .into());
+        }
+        Ok(Self {
+            bytes_per_sector_shift,
+            sectors_per_cluster_shift,
+            volume_length: volume_sectors,
+            number_of_fats,
+            fat_offset: fat_offset as u32,
+            fat_length: fat_length as u32,
+            cluster_heap_offset: cluster_heap_offset as u32,
+            cluster_count: cluster_count as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        shift::{ShiftedBytes, ShiftedSectors},
+        super_block::SuperBlock,
+    };
+
+    use super::{Geometry, MAX_CLUSTER_COUNT};
+
+    fn check(geometry: &Geometry, alignment: u32) {
+        let bytes_per_sector = 1u64 << geometry.bytes_per_sector_shift.inner();
+        let sectors_per_cluster =
+            1u64 << geometry.sectors_per_cluster_shift.inner();
+        assert!(geometry.fat_offset >= 24);
+        assert_eq!(geometry.fat_offset % alignment, 0);
+        assert_eq!(geometry.cluster_heap_offset % alignment, 0);
+        let fat_end = geometry.fat_offset as u64
+            + geometry.fat_length as u64 * geometry.number_of_fats as u64;
+        assert!(fat_end <= geometry.cluster_heap_offset as u64);
+        let needed = (geometry.cluster_count as u64 + 2) * 4;
+        assert!(geometry.fat_length as u64 * bytes_per_sector >= needed);
+        // the FAT is no longer than it needs to be
+        assert!((geometry.fat_length as u64 - 1) * bytes_per_sector < needed);
+        assert!(geometry.cluster_count >= 1);
+        assert!(geometry.cluster_count <= MAX_CLUSTER_COUNT);
+        let heap_end = geometry.cluster_heap_offset as u64
+            + geometry.cluster_count as u64 * sectors_per_cluster;
+        assert!(heap_end <= geometry.volume_length);
+        if geometry.cluster_count < MAX_CLUSTER_COUNT {
+            assert!(
+                geometry.volume_length - heap_end < sectors_per_cluster,
+                "the cluster heap should span the rest of the volume"
+            );
+        }
+        SuperBlock::from_geometry(geometry)
+            .verify_bounds()
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    #[test]
+    pub fn every_shift() {
+        let volume_lengths = [
+            1u64 << 20,
+            (1 << 20) + 4095,
+            (1 << 25) + 12345,
+            1 << 36,
+            1 << 44,
+            u64::MAX,
+        ];
+        for bytes_shift in 9..=12u8 {
+            for sectors_shift in 0..=25 - bytes_shift {
+                let cluster_size = 1u64 << (bytes_shift + sectors_shift);
+                for number_of_fats in 1..=2 {
+                    for alignment_shift in [0, sectors_shift, 13] {
+                        let alignment = 1u32 << alignment_shift;
+                        for volume_length in volume_lengths {
+                            let planned = Geometry::plan(
+                                volume_length,
+                                ShiftedBytes::new(bytes_shift).into(),
+                                ShiftedSectors::from(sectors_shift).into(),
+                                number_of_fats,
+                                alignment,
+                            );
+                            match planned {
+                                Ok(geometry) => check(&geometry, alignment),
+                                Err(Error::Bound(_)) => {
+                                    // only a volume with no room for a
+                                    // single cluster may be rejected
+                                    let alignment = alignment as u64;
+                                    let overhead = (24u64
+                                        .next_multiple_of(alignment)
+                                        + number_of_fats as u64)
+                                        .next_multiple_of(alignment)
+                                        << bytes_shift;
+                                    assert!(
+                                        volume_length < overhead + cluster_size,
+                                        "{} byte volume with {} byte \
+                                        sectors and {} byte clusters \
+                                        rejected",
+                                        volume_length,
+                                        1 << bytes_shift,
+                                        cluster_size
+                                    );
+                                }
+                                Err(e) => panic!("{}", e),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn impossible_requests() {
+        let plan = |volume_length, bytes_shift, sectors_shift, fats, align| {
+            Geometry::plan(
+                volume_length,
+                ShiftedBytes::new(bytes_shift).into(),
+                ShiftedSectors::from(sectors_shift).into(),
+                fats,
+                align,
+            )
+        };
+        // smaller than 1MB
+        assert!(matches!(plan(1 << 19, 9, 0, 1, 1), Err(Error::Bound(_))));
+        // 64MB clusters
+        assert!(matches!(plan(1 << 40, 12, 14, 1, 1), Err(Error::Bound(_))));
+        // 8KB sectors
+        assert!(matches!(plan(1 << 40, 13, 0, 1, 1), Err(Error::Bound(_))));
+        assert!(matches!(plan(1 << 40, 9, 0, 0, 1), Err(Error::Bound(_))));
+        assert!(matches!(plan(1 << 40, 9, 0, 3, 1), Err(Error::Bound(_))));
+        assert!(matches!(
+            plan(1 << 40, 9, 0, 1, 3),
+            Err(Error::NotPowerOfTwo { .. })
+        ));
+        // a 1MB volume cannot hold a 32MB cluster
+        assert!(matches!(plan(1 << 20, 9, 16, 1, 1), Err(Error::Bound(_))));
+        // the cluster heap cannot start beyond sector 2**32 - 1
+        assert!(matches!(
+            plan(u64::MAX, 9, 0, 1, 1 << 31),
+            Err(Error::Bound(_))
+        ));
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Clone)]
-pub(super) struct VolumeFlags {
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct VolumeFlags {
     flags: u16,
 }
 
@@ -17,7 +17,7 @@ impl VolumeFlags {
     /// Implementations shall consider the inactive FAT and Allocation Bitmap as
     /// stale. Only TexFAT-aware implementations shall switch the active FAT and
     /// Allocation Bitmaps (see Section 7.1).
-    const ACTIVE_FAT: u16 = 0b10000000;
+    const ACTIVE_FAT: u16 = 0b0001;
     /// The VolumeDirty field shall describe whether the volume is dirty or not,
     /// as follows:
     ///
@@ -37,10 +37,10 @@ impl VolumeFlags {
     /// implementations should set this field to 1 before updating file system
     /// metadata and clear this field to 0 afterwards, similar to the
     /// recommended write ordering described in Section 8.1.
-    const VOLUME_DIRTY: u16 = 0b01000000;
+    const VOLUME_DIRTY: u16 = 0b0010;
     /// The MediaFailure field shall describe whether an implementation has
     /// discovered media failures or not, as follows:
-
+    ///
     /// - 0, which means the hosting media has not reported failures or any
     ///   known failures are already recorded in the FAT as "bad" clusters
     ///
@@ -52,12 +52,12 @@ impl VolumeFlags {
     /// 1. The hosting media fails access attempts to any region in the volume
     ///
     /// 2. The implementation has exhausted access retry algorithms, if any
-
+    ///
     /// If, upon mounting a volume, the value of this field is 1,
     /// implementations which scan the entire volume for media failures and
     /// record all failures as "bad" clusters in the FAT (or otherwise resolve
     /// media failures) may clear the value of this field to 0.
-    const MEDIA_FAILURE: u16 = 0b00100000;
+    const MEDIA_FAILURE: u16 = 0b0100;
     /// The ClearToZero field does not have significant meaning in this
     /// specification.
     ///
@@ -67,7 +67,7 @@ impl VolumeFlags {
     ///
     /// - 1, which means implementations shall clear this field to 0 prior to
     ///   modifying any file system structures, directories, or files
-    pub const CLEAR_TO_ZERO: u16 = 0b00010000;
+    pub const CLEAR_TO_ZERO: u16 = 0b1000;
 
     fn set_flag(&mut self, flag: u16, on: bool) {
         if on {
@@ -77,6 +77,20 @@ impl VolumeFlags {
         }
     }
 
+    /// Whether the Second FAT and Second Allocation Bitmap are active.
+    pub fn active_fat(&self) -> bool {
+        self.flags & Self::ACTIVE_FAT != 0
+    }
+    pub fn volume_dirty(&self) -> bool {
+        self.flags & Self::VOLUME_DIRTY != 0
+    }
+    pub fn media_failure(&self) -> bool {
+        self.flags & Self::MEDIA_FAILURE != 0
+    }
+    pub fn clear_to_zero(&self) -> bool {
+        self.flags & Self::CLEAR_TO_ZERO != 0
+    }
+
     pub fn set_active_fat(&mut self, second_active: bool) {
         self.set_flag(Self::ACTIVE_FAT, second_active);
     }
@@ -90,11 +104,3 @@ impl VolumeFlags {
         self.set_flag(Self::CLEAR_TO_ZERO, clear_before_modification);
     }
 }
-
-impl Default for VolumeFlags {
-    fn default() -> Self {
-        Self {
-            flags: Default::default(),
-        }
-    }
-}
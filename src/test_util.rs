@@ -0,0 +1,69 @@
+//! # Purpose
+//! Helpers the tests of several modules share: formatting a volume held in
+//! memory and spelling names as UTF-16.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+pub use self::disk::*;
+
+pub fn utf16(name: &str) -> Vec<u16> {
+    name.encode_utf16().collect()
+}
+
+#[cfg(feature = "std")]
+mod disk {
+    use std::io::Cursor;
+
+    use alloc::vec::Vec;
+
+    use crate::{
+        block_device::IoDevice,
+        format_options::FormatOptions,
+        shift::{ShiftedBytes, ShiftedSectors},
+        time::TimeSource,
+        Error, FileSystem,
+    };
+
+    /// The size of the disk [`format`] formats.
+    pub const DISK_SIZE: usize = 2usize.pow(24);
+
+    /// A volume held in memory.
+    pub type MemoryDisk = IoDevice<Cursor<Vec<u8>>>;
+
+    /// Formats a zeroed disk of [`DISK_SIZE`] bytes with 512 byte sectors and
+    /// 4KB clusters, which puts the root directory at cluster 4.
+    pub fn format() -> FileSystem<MemoryDisk> {
+        format_with(
+            alloc::vec![0; DISK_SIZE],
+            ShiftedSectors::from(3),
+            &FormatOptions::default(),
+        )
+    }
+
+    /// Formats all of `image` with 512 byte sectors.
+    pub fn format_with(
+        image: Vec<u8>,
+        sectors_per_cluster: ShiftedSectors,
+        options: &FormatOptions<impl TimeSource + Clone + 'static>,
+    ) -> FileSystem<MemoryDisk> {
+        try_format_with(image, sectors_per_cluster, options).unwrap()
+    }
+
+    /// [`format_with`] for tests of formatting failing.
+    pub fn try_format_with(
+        image: Vec<u8>,
+        sectors_per_cluster: ShiftedSectors,
+        options: &FormatOptions<impl TimeSource + Clone + 'static>,
+    ) -> Result<FileSystem<MemoryDisk>, Error> {
+        let volume_length = image.len() as u64;
+        let disk = IoDevice::new(Cursor::new(image)).unwrap();
+        FileSystem::format(
+            ShiftedBytes::new(9).unshift(),
+            sectors_per_cluster.into(),
+            volume_length,
+            options,
+            disk,
+        )
+    }
+}
@@ -0,0 +1,548 @@
+//! # Purpose
+//! Defines [`TimeSource`], where the file system gets the current date and
+//! time from: when formatting, to derive the volume serial number, and when
+//! stamping files. [`FixedTime`] always reports the same instant, which makes
+//! output reproducible and works on targets without a clock, while
+//! `SystemClock` reads the system clock when the `std` feature is enabled.
+//!
+//! [`Timestamp`] is how File directory entries record when a file was
+//! created, modified and accessed: a packed DOS date and time counting every
+//! other second, a 10msIncrement adding the odd second and the hundredths,
+//! and a UtcOffset giving the time zone in 15 minute steps.
+
+use core::fmt::Display;
+
+/// Ways a date and time can fail to fit an exFAT timestamp.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The named field is outside of the range it can take, such as a year
+    /// before 1980 or after 2107, or the 30th of February.
+    OutOfRange { field: &'static str, value: i32 },
+    /// A 10msIncrement field past 199.
+    Increment(u8),
+    /// A UtcOffset field with OffsetValid cleared but an offset given
+    /// anyway.
+    UtcOffset(u8),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::OutOfRange { field, value } => write!(
+                f,
+                "A {} of {} cannot be held by an exFAT timestamp.",
+                field, value
+            ),
+            Error::Increment(increment) => write!(
+                f,
+                "A 10ms increment of {} is past the maximum of 199.",
+                increment
+            ),
+            Error::UtcOffset(offset) => write!(
+                f,
+                "UTC offset {:#04x} gives an offset without marking it valid.",
+                offset
+            ),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// A calendar date and time of day, in UTC unless it is part of a
+/// [`Timestamp`] with another offset.
+///
+/// Defaults to 1980-01-01T00:00:00Z, the earliest instant an exFAT timestamp
+/// can hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    /// 1 through 12.
+    pub month: u8,
+    /// 1 through 31.
+    pub day: u8,
+    /// 0 through 23.
+    pub hour: u8,
+    /// 0 through 59.
+    pub minute: u8,
+    /// 0 through 59.
+    pub second: u8,
+    /// 0 through 999.
+    pub millisecond: u16,
+}
+
+impl Default for DateTime {
+    fn default() -> Self {
+        Self {
+            year: 1980,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            millisecond: 0,
+        }
+    }
+}
+
+impl DateTime {
+    /// Converts a duration since 1970-01-01T00:00:00Z into a calendar date
+    /// and time.
+    pub fn from_unix(seconds: u64, millisecond: u16) -> Self {
+        let days = (seconds / 86400) as i64;
+        let time = seconds % 86400;
+        // converts days since the epoch to a civil date, see
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+            - day_of_era / 146096)
+            / 365;
+        let day_of_year = day_of_era
+            - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+            millisecond,
+        }
+    }
+}
+
+impl DateTime {
+    /// The number of seconds from 1970-01-01T00:00:00Z to the start of the
+    /// second, which saturates at 0 for earlier dates.
+    pub fn to_unix(&self) -> u64 {
+        // converts a civil date to days since the epoch, see
+        // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+        let year = self.year as i64 - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let shifted_month = (self.month as i64 + 9) % 12;
+        let day_of_year = (153 * shifted_month + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4
+            - year_of_era / 100
+            + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+        let seconds = days * 86400
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64;
+        seconds.max(0) as u64
+    }
+
+    /// Checks that every field is within its range, and the year within the
+    /// 1980 through 2107 an exFAT timestamp can hold.
+    pub fn validate(&self) -> Result<(), Error> {
+        let check =
+            |field, value: i32, range: core::ops::RangeInclusive<i32>| {
+                if range.contains(&value) {
+                    Ok(())
+                } else {
+                    Err(Error::OutOfRange { field, value })
+                }
+            };
+        check("year", self.year as i32, 1980..=2107)?;
+        check("month", self.month as i32, 1..=12)?;
+        check("day", self.day as i32, 1..=days_in_month(self) as i32)?;
+        check("hour", self.hour as i32, 0..=23)?;
+        check("minute", self.minute as i32, 0..=59)?;
+        check("second", self.second as i32, 0..=59)?;
+        check("millisecond", self.millisecond as i32, 0..=999)
+    }
+}
+
+fn days_in_month(date: &DateTime) -> u8 {
+    match date.month {
+        2 if date.year.is_multiple_of(4)
+            && (!date.year.is_multiple_of(100)
+                || date.year.is_multiple_of(400)) =>
+        {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// When a file was created, modified or accessed, as recorded by a File
+/// directory entry.
+///
+/// A timestamp holds the date and time to 10 milliseconds, from 1980 through
+/// 2107, in the time zone given by its offset from UTC. Decoding and
+/// encoding the fields of a directory entry gives back the same bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp {
+    date_time: DateTime,
+    utc_offset: Option<i8>,
+}
+
+impl Timestamp {
+    /// 1980-01-01T00:00:00Z.
+    pub const MIN: Self = Self {
+        date_time: DateTime {
+            year: 1980,
+            month: 1,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            millisecond: 0,
+        },
+        utc_offset: Some(0),
+    };
+    /// 2107-12-31T23:59:59.990Z.
+    pub const MAX: Self = Self {
+        date_time: DateTime {
+            year: 2107,
+            month: 12,
+            day: 31,
+            hour: 23,
+            minute: 59,
+            second: 59,
+            millisecond: 990,
+        },
+        utc_offset: Some(0),
+    };
+
+    /// The bit of a UtcOffset field which says the offset is given.
+    const OFFSET_VALID: u8 = 0x80;
+
+    /// A timestamp at `date_time` in the time zone `utc_offset` steps of 15
+    /// minutes ahead of UTC, -64 through 63, or in an unknown time zone for
+    /// `None`. Milliseconds are rounded down to the 10 an exFAT timestamp
+    /// counts.
+    pub fn new(
+        date_time: DateTime,
+        utc_offset: Option<i8>,
+    ) -> Result<Self, Error> {
+        date_time.validate()?;
+        if let Some(offset) = utc_offset {
+            if !(-64..=63).contains(&offset) {
+                return Err(Error::OutOfRange {
+                    field: "UTC offset",
+                    value: offset as i32,
+                });
+            }
+        }
+        Ok(Self {
+            date_time: DateTime {
+                millisecond: date_time.millisecond / 10 * 10,
+                ..date_time
+            },
+            utc_offset,
+        })
+    }
+
+    /// A timestamp at `date_time` in UTC.
+    pub fn utc(date_time: DateTime) -> Result<Self, Error> {
+        Self::new(date_time, Some(0))
+    }
+
+    /// A timestamp at `date_time` in UTC, or the earliest or latest one if
+    /// `date_time` is before or after the range a timestamp can hold.
+    pub fn saturating_utc(date_time: DateTime) -> Self {
+        Self::utc(date_time).unwrap_or(if date_time < Self::MIN.date_time {
+            Self::MIN
+        } else {
+            Self::MAX
+        })
+    }
+
+    /// The date and time in the time zone of the timestamp.
+    pub fn date_time(&self) -> DateTime {
+        self.date_time
+    }
+
+    /// How many steps of 15 minutes the time zone of the timestamp is ahead
+    /// of UTC, if known.
+    pub fn utc_offset(&self) -> Option<i8> {
+        self.utc_offset
+    }
+
+    /// The date and time in UTC. A timestamp in an unknown time zone is
+    /// taken to be in UTC already.
+    pub fn to_utc(&self) -> DateTime {
+        let offset = self.utc_offset.unwrap_or(0) as i64 * 15 * 60;
+        let seconds = self.date_time.to_unix() as i64 - offset;
+        DateTime::from_unix(seconds as u64, self.date_time.millisecond)
+    }
+
+    /// Decodes the Timestamp, 10msIncrement and UtcOffset fields of a File
+    /// directory entry. LastAccessed has no 10msIncrement field, for which
+    /// 0 is given.
+    pub fn decode(
+        timestamp: u32,
+        increment: u8,
+        utc_offset: u8,
+    ) -> Result<Self, Error> {
+        if increment > 199 {
+            return Err(Error::Increment(increment));
+        }
+        let utc_offset = if utc_offset & Self::OFFSET_VALID != 0 {
+            // sign extends the 7 bit offset
+            Some((utc_offset << 1) as i8 >> 1)
+        } else if utc_offset == 0 {
+            None
+        } else {
+            return Err(Error::UtcOffset(utc_offset));
+        };
+        let date_time = DateTime {
+            year: 1980 + (timestamp >> 25) as u16,
+            month: (timestamp >> 21 & 0xF) as u8,
+            day: (timestamp >> 16 & 0x1F) as u8,
+            hour: (timestamp >> 11 & 0x1F) as u8,
+            minute: (timestamp >> 5 & 0x3F) as u8,
+            second: (timestamp & 0x1F) as u8 * 2 + increment / 100,
+            millisecond: (increment % 100) as u16 * 10,
+        };
+        Self::new(date_time, utc_offset)
+    }
+
+    /// Encodes the timestamp into the Timestamp, 10msIncrement and UtcOffset
+    /// fields of a File directory entry.
+    pub fn encode(&self) -> (u32, u8, u8) {
+        let date_time = &self.date_time;
+        let date = ((date_time.year - 1980) as u32) << 9
+            | (date_time.month as u32) << 5
+            | date_time.day as u32;
+        let time = (date_time.hour as u32) << 11
+            | (date_time.minute as u32) << 5
+            | (date_time.second / 2) as u32;
+        let increment =
+            (date_time.second % 2) * 100 + (date_time.millisecond / 10) as u8;
+        let utc_offset = self.utc_offset.map_or(0, |offset| {
+            Self::OFFSET_VALID | (offset as u8 & !Self::OFFSET_VALID)
+        });
+        (date << 16 | time, increment, utc_offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<std::time::SystemTime> for Timestamp {
+    type Error = Error;
+
+    /// Converts to a timestamp in UTC, failing for instants before 1980 or
+    /// after 2107.
+    fn try_from(value: std::time::SystemTime) -> Result<Self, Error> {
+        let since_epoch =
+            value.duration_since(std::time::UNIX_EPOCH).map_err(|_| {
+                Error::OutOfRange {
+                    field: "year",
+                    value: 1969,
+                }
+            })?;
+        Self::utc(DateTime::from_unix(
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis() as u16,
+        ))
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Timestamp> for std::time::SystemTime {
+    fn from(value: Timestamp) -> Self {
+        let utc = value.to_utc();
+        std::time::UNIX_EPOCH
+            + std::time::Duration::from_millis(
+                utc.to_unix() * 1000 + utc.millisecond as u64,
+            )
+    }
+}
+
+/// Tells the file system what the current date and time is.
+pub trait TimeSource {
+    /// The current date and time in UTC.
+    fn now(&self) -> DateTime;
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now(&self) -> DateTime {
+        (**self).now()
+    }
+}
+
+/// A [`TimeSource`] that is stuck at one instant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FixedTime(pub DateTime);
+
+impl TimeSource for FixedTime {
+    fn now(&self) -> DateTime {
+        self.0
+    }
+}
+
+/// A [`TimeSource`] backed by [`std::time::SystemTime`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl TimeSource for SystemClock {
+    fn now(&self) -> DateTime {
+        let since_epoch = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        DateTime::from_unix(
+            since_epoch.as_secs(),
+            since_epoch.subsec_millis() as u16,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DateTime, Error, Timestamp};
+
+    #[test]
+    pub fn from_unix() {
+        assert_eq!(
+            DateTime::from_unix(0, 0),
+            DateTime {
+                year: 1970,
+                month: 1,
+                day: 1,
+                ..Default::default()
+            }
+        );
+        // a leap day
+        assert_eq!(
+            DateTime::from_unix(951_827_696, 250),
+            DateTime {
+                year: 2000,
+                month: 2,
+                day: 29,
+                hour: 12,
+                minute: 34,
+                second: 56,
+                millisecond: 250,
+            }
+        );
+        assert_eq!(
+            DateTime::from_unix(4_102_444_799, 0),
+            DateTime {
+                year: 2099,
+                month: 12,
+                day: 31,
+                hour: 23,
+                minute: 59,
+                second: 59,
+                millisecond: 0,
+            }
+        );
+    }
+
+    #[test]
+    pub fn timestamps() {
+        let date_time = DateTime {
+            year: 2024,
+            month: 6,
+            day: 30,
+            hour: 12,
+            minute: 34,
+            second: 57,
+            millisecond: 890,
+        };
+        // UTC+05:30
+        let timestamp = Timestamp::new(date_time, Some(22)).unwrap();
+        assert_eq!(timestamp.encode(), (0x58DE_645C, 189, 0x96));
+        assert_eq!(Timestamp::decode(0x58DE_645C, 189, 0x96), Ok(timestamp));
+        assert_eq!(
+            timestamp.to_utc(),
+            DateTime {
+                hour: 7,
+                minute: 4,
+                ..date_time
+            }
+        );
+        // UTC-03:00 across midnight, and an unknown time zone
+        let west = Timestamp::new(DateTime::default(), Some(-12)).unwrap();
+        assert_eq!(west.encode(), (0x0021_0000, 0, 0xF4));
+        assert_eq!(Timestamp::decode(0x0021_0000, 0, 0xF4), Ok(west));
+        assert_eq!(west.to_utc().hour, 3);
+        let unknown = Timestamp::decode(0x0021_0000, 0, 0).unwrap();
+        assert_eq!(unknown.utc_offset(), None);
+        assert_eq!(unknown.encode(), (0x0021_0000, 0, 0));
+
+        // every field is checked
+        assert_eq!(
+            Timestamp::utc(DateTime {
+                year: 2023,
+                month: 2,
+                day: 29,
+                ..date_time
+            }),
+            Err(Error::OutOfRange {
+                field: "day",
+                value: 29
+            })
+        );
+        assert_eq!(
+            Timestamp::utc(DateTime::from_unix(0, 0)),
+            Err(Error::OutOfRange {
+                field: "year",
+                value: 1970
+            })
+        );
+        assert!(Timestamp::new(date_time, Some(64)).is_err());
+        assert_eq!(
+            Timestamp::decode(0x58DE_645C, 200, 0x80),
+            Err(Error::Increment(200))
+        );
+        assert_eq!(
+            Timestamp::decode(0x58DE_645C, 0, 0x16),
+            Err(Error::UtcOffset(0x16))
+        );
+        // the 30th double second, the 13th month and the 0th day
+        for invalid in [0x58DE_645E, 0x59BE_645C, 0x58C0_645C] {
+            assert!(Timestamp::decode(invalid, 0, 0x80).is_err());
+        }
+        assert_eq!(
+            Timestamp::saturating_utc(DateTime::from_unix(0, 0)),
+            Timestamp::MIN
+        );
+        assert_eq!(
+            Timestamp::saturating_utc(DateTime {
+                year: 2108,
+                ..date_time
+            }),
+            Timestamp::MAX
+        );
+        let max = Timestamp::MAX.encode();
+        assert_eq!(Timestamp::decode(max.0, max.1, max.2), Ok(Timestamp::MAX));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    pub fn system_time() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let time = UNIX_EPOCH + Duration::from_millis(951_827_696_250);
+        let timestamp = Timestamp::try_from(time).unwrap();
+        assert_eq!(
+            timestamp.date_time(),
+            DateTime::from_unix(951_827_696, 250)
+        );
+        assert_eq!(SystemTime::from(timestamp), time);
+        let east = Timestamp::new(timestamp.date_time(), Some(4)).unwrap();
+        assert_eq!(SystemTime::from(east), time - Duration::from_secs(60 * 60));
+        assert!(Timestamp::try_from(UNIX_EPOCH).is_err());
+        assert_eq!(
+            DateTime::from_unix(4_102_444_799, 0).to_unix(),
+            4_102_444_799
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! # Purpose
+//! Generates, compresses and loads the Up-case Table, which maps every UTF-16
+//! code unit to its upper case so that file names can be compared without
+//! regard to case.
+//!
+//! The table is stored on disk as a sequence of little endian code units,
+//! the one at index `n` being the upper case of code unit `n`. A code unit of
+//! FFFFh followed by a count compresses that many code units which map to
+//! themselves.
+
+use core::{cmp::Ordering, fmt::Display};
+
+use alloc::vec::Vec;
+
+use crate::{
+    block_device::BlockDevice, cluster_heap::ClusterHeap,
+    dir_entry::UpcaseTableEntry, fat::Fat, stream,
+};
+
+/// Ways an Up-case Table can be unusable.
+#[derive(Debug)]
+pub enum Error {
+    /// The root directory has no Up-case Table directory entry.
+    Missing,
+    /// The TableChecksum of the Up-case Table directory entry does not match
+    /// the table.
+    ChecksumMismatch { expected: u32, calculated: u32 },
+    /// The table describes more than the 65536 possible code units.
+    TooLong,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Missing => {
+                write!(f, "The root directory has no up-case table.")
+            }
+            Error::ChecksumMismatch {
+                expected,
+                calculated,
+            } => write!(
+                f,
+                "Up-case table checksum {:#010x} does not match the \
+                calculated checksum {:#010x}.",
+                expected, calculated
+            ),
+            Error::TooLong => {
+                write!(f, "The up-case table maps more than 65536 code units.")
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Calculates the TableChecksum of an Up-case Table from its bytes as stored
+/// on disk.
+pub fn table_checksum(table: &[u8]) -> u32 {
+    table.iter().fold(0u32, |checksum, byte| {
+        checksum.rotate_right(1).wrapping_add(*byte as u32)
+    })
+}
+
+// The simple upper case mappings of Unicode 5.0 within the Basic Multilingual
+// Plane, except for those of U+00B5 MICRO SIGN, U+0131 LATIN SMALL LETTER
+// DOTLESS I, U+017F LATIN SMALL LETTER LONG S, the titlecase digraphs, the
+// Greek iota subscripts and symbol variants, and U+1E9B, which map to the
+// upper case of another letter. They are fixed here rather than taken from
+// `core` so that formatting writes the same table whatever Unicode version
+// the toolchain implements.
+
+/// Ranges `start..end` of code units whose upper case is `offset` away.
+const RUNS: [(u16, u16, i16); 47] = [
+    (0x0061, 0x007B, -32),
+    (0x00E0, 0x00F7, -32),
+    (0x00F8, 0x00FF, -32),
+    (0x0256, 0x0258, -205),
+    (0x028A, 0x028C, -217),
+    (0x037B, 0x037E, 130),
+    (0x03AC, 0x03AD, -38),
+    (0x03AD, 0x03B0, -37),
+    (0x03B1, 0x03C2, -32),
+    (0x03C2, 0x03C3, -31),
+    (0x03C3, 0x03CC, -32),
+    (0x03CC, 0x03CD, -64),
+    (0x03CD, 0x03CF, -63),
+    (0x0430, 0x0450, -32),
+    (0x0450, 0x0460, -80),
+    (0x0561, 0x0587, -48),
+    (0x1F00, 0x1F08, 8),
+    (0x1F10, 0x1F16, 8),
+    (0x1F20, 0x1F28, 8),
+    (0x1F30, 0x1F38, 8),
+    (0x1F40, 0x1F46, 8),
+    (0x1F51, 0x1F52, 8),
+    (0x1F53, 0x1F54, 8),
+    (0x1F55, 0x1F56, 8),
+    (0x1F57, 0x1F58, 8),
+    (0x1F60, 0x1F68, 8),
+    (0x1F70, 0x1F72, 74),
+    (0x1F72, 0x1F76, 86),
+    (0x1F76, 0x1F78, 100),
+    (0x1F78, 0x1F7A, 128),
+    (0x1F7A, 0x1F7C, 112),
+    (0x1F7C, 0x1F7E, 126),
+    (0x1F80, 0x1F88, 8),
+    (0x1F90, 0x1F98, 8),
+    (0x1FA0, 0x1FA8, 8),
+    (0x1FB0, 0x1FB2, 8),
+    (0x1FB3, 0x1FB4, 9),
+    (0x1FC3, 0x1FC4, 9),
+    (0x1FD0, 0x1FD2, 8),
+    (0x1FE0, 0x1FE2, 8),
+    (0x1FE5, 0x1FE6, 7),
+    (0x1FF3, 0x1FF4, 9),
+    (0x2170, 0x2180, -16),
+    (0x24D0, 0x24EA, -26),
+    (0x2C30, 0x2C5F, -48),
+    (0x2D00, 0x2D26, -7264),
+    (0xFF41, 0xFF5B, -32),
+];
+
+/// Ranges `start..end` of alternating upper and lower case letters, every
+/// lower case letter following its upper case.
+const PAIRS: [(u16, u16); 29] = [
+    (0x0100, 0x0130),
+    (0x0132, 0x0138),
+    (0x0139, 0x0149),
+    (0x014A, 0x0178),
+    (0x0179, 0x017F),
+    (0x01A0, 0x01A6),
+    (0x01B3, 0x01B7),
+    (0x01CD, 0x01DD),
+    (0x01DE, 0x01F0),
+    (0x01F4, 0x01F6),
+    (0x01F8, 0x0220),
+    (0x0222, 0x0234),
+    (0x023B, 0x023D),
+    (0x0241, 0x0243),
+    (0x0246, 0x0250),
+    (0x03D8, 0x03F0),
+    (0x03F7, 0x03F9),
+    (0x03FA, 0x03FC),
+    (0x0460, 0x0482),
+    (0x048A, 0x04C0),
+    (0x04C1, 0x04CF),
+    (0x04D0, 0x0514),
+    (0x1E00, 0x1E96),
+    (0x1EA0, 0x1EFA),
+    (0x2183, 0x2185),
+    (0x2C60, 0x2C62),
+    (0x2C67, 0x2C6D),
+    (0x2C75, 0x2C77),
+    (0x2C80, 0x2CE4),
+];
+
+/// Code units and their upper case which fit none of the ranges.
+const SINGLES: [(u16, u16); 47] = [
+    (0x00FF, 0x0178),
+    (0x0180, 0x0243),
+    (0x0183, 0x0182),
+    (0x0185, 0x0184),
+    (0x0188, 0x0187),
+    (0x018C, 0x018B),
+    (0x0192, 0x0191),
+    (0x0195, 0x01F6),
+    (0x0199, 0x0198),
+    (0x019A, 0x023D),
+    (0x019E, 0x0220),
+    (0x01A8, 0x01A7),
+    (0x01AD, 0x01AC),
+    (0x01B0, 0x01AF),
+    (0x01B9, 0x01B8),
+    (0x01BD, 0x01BC),
+    (0x01BF, 0x01F7),
+    (0x01C6, 0x01C4),
+    (0x01C9, 0x01C7),
+    (0x01CC, 0x01CA),
+    (0x01DD, 0x018E),
+    (0x01F3, 0x01F1),
+    (0x0253, 0x0181),
+    (0x0254, 0x0186),
+    (0x0259, 0x018F),
+    (0x025B, 0x0190),
+    (0x0260, 0x0193),
+    (0x0263, 0x0194),
+    (0x0268, 0x0197),
+    (0x0269, 0x0196),
+    (0x026B, 0x2C62),
+    (0x026F, 0x019C),
+    (0x0272, 0x019D),
+    (0x0275, 0x019F),
+    (0x027D, 0x2C64),
+    (0x0280, 0x01A6),
+    (0x0283, 0x01A9),
+    (0x0288, 0x01AE),
+    (0x0289, 0x0244),
+    (0x028C, 0x0245),
+    (0x0292, 0x01B7),
+    (0x03F2, 0x03F9),
+    (0x04CF, 0x04C0),
+    (0x1D7D, 0x2C63),
+    (0x214E, 0x2132),
+    (0x2C65, 0x023A),
+    (0x2C66, 0x023E),
+];
+
+/// A decompressed Up-case Table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpcaseTable {
+    /// The upper case of every code unit below the length of the table. Code
+    /// units past its end map to themselves.
+    mapping: Vec<u16>,
+}
+
+impl UpcaseTable {
+    /// The code unit introducing a run of code units which map to
+    /// themselves.
+    const IDENTITY_RUN: u16 = 0xFFFF;
+    /// The shortest run of code units mapping to themselves worth
+    /// compressing.
+    const MIN_RUN: usize = 3;
+
+    /// The up-case table formatting writes: every code unit maps to its
+    /// upper case as given by `RUNS`, `PAIRS` and `SINGLES`, or to
+    /// itself if it has none.
+    pub fn generate() -> Self {
+        let mut mapping: Vec<u16> = (0..=u16::MAX).collect();
+        for (start, end, offset) in RUNS {
+            for unit in start..end {
+                mapping[unit as usize] = unit.wrapping_add_signed(offset);
+            }
+        }
+        for (start, end) in PAIRS {
+            for upper in (start..end - 1).step_by(2) {
+                mapping[upper as usize + 1] = upper;
+            }
+        }
+        for (unit, upper) in SINGLES {
+            mapping[unit as usize] = upper;
+        }
+        Self { mapping }
+    }
+
+    /// Expands a table as stored on disk, compressed or not.
+    pub fn decompress(table: &[u8]) -> Result<Self, Error> {
+        let mut units = table
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]));
+        let mut mapping = Vec::new();
+        while let Some(unit) = units.next() {
+            match (unit, units.clone().next()) {
+                (Self::IDENTITY_RUN, Some(count)) => {
+                    units.next();
+                    let start = mapping.len();
+                    mapping.extend(
+                        (start..start + count as usize).map(|unit| unit as u16),
+                    );
+                }
+                // a lone FFFFh at the very end of an uncompressed table is
+                // the upper case of FFFFh
+                _ => mapping.push(unit),
+            }
+            if mapping.len() > 1 << 16 {
+                return Err(Error::TooLong);
+            }
+        }
+        Ok(Self { mapping })
+    }
+
+    /// The table as it is stored on disk, with runs of code units which map
+    /// to themselves compressed.
+    pub fn compress(&self) -> Vec<u8> {
+        let mut units = Vec::new();
+        let mut index = 0;
+        while index < self.mapping.len() {
+            let run = self.mapping[index..]
+                .iter()
+                .zip(index..)
+                .take_while(|(upper, unit)| **upper as usize == *unit)
+                .take(u16::MAX as usize)
+                .count();
+            // FFFFh on its own would start a run, so it is always part of one
+            let ends_table = index + run == 1 << 16;
+            if run >= Self::MIN_RUN || (run > 0 && ends_table) {
+                units.extend([Self::IDENTITY_RUN, run as u16]);
+                index += run;
+            } else {
+                units.push(self.mapping[index]);
+                index += 1;
+            }
+        }
+        units.iter().flat_map(|unit| unit.to_le_bytes()).collect()
+    }
+
+    /// Reads and verifies the Up-case Table an [`UpcaseTableEntry`] points
+    /// to.
+    pub fn load(
+        heap: ClusterHeap,
+        fat: &Fat,
+        disk: &mut impl BlockDevice,
+        entry: &UpcaseTableEntry,
+    ) -> Result<Self, crate::Error> {
+        let clusters = fat
+            .chain(disk, entry.first_cluster)
+            .take(heap.clusters_for(entry.data_length) as usize)
+            .collect::<Result<Vec<_>, _>>()?;
+        if (clusters.len() as u64) < heap.clusters_for(entry.data_length) {
+            return Err(stream::Error::TooShort {
+                data_length: entry.data_length,
+                clusters: clusters.len(),
+            }
+            .into());
+        }
+        let mut table = alloc::vec![0u8; entry.data_length as usize];
+        for (cluster, chunk) in clusters
+            .iter()
+            .zip(table.chunks_mut(heap.bytes_per_cluster() as usize))
+        {
+            disk.read_at(heap.cluster_offset(*cluster), chunk)?;
+        }
+        let calculated = table_checksum(&table);
+        if calculated != entry.table_checksum {
+            return Err(Error::ChecksumMismatch {
+                expected: entry.table_checksum,
+                calculated,
+            }
+            .into());
+        }
+        Ok(Self::decompress(&table)?)
+    }
+
+    /// The upper case of `unit`.
+    pub fn upcase(&self, unit: u16) -> u16 {
+        self.mapping.get(unit as usize).copied().unwrap_or(unit)
+    }
+
+    /// Compares two UTF-16 names as the file system does, after mapping
+    /// every code unit of both to its upper case.
+    pub fn cmp_ignore_case(&self, lhs: &[u16], rhs: &[u16]) -> Ordering {
+        lhs.iter()
+            .map(|unit| self.upcase(*unit))
+            .cmp(rhs.iter().map(|unit| self.upcase(*unit)))
+    }
+
+    /// Whether two UTF-16 names name the same file.
+    pub fn eq_ignore_case(&self, lhs: &[u16], rhs: &[u16]) -> bool {
+        lhs.len() == rhs.len()
+            && self.cmp_ignore_case(lhs, rhs) == Ordering::Equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::test_util::utf16;
+
+    use super::{table_checksum, Error, UpcaseTable};
+
+    #[test]
+    pub fn generate_and_compress() {
+        let table = UpcaseTable::generate();
+        assert_eq!(table.upcase(b'a' as u16), b'A' as u16);
+        assert_eq!(table.upcase(b'A' as u16), b'A' as u16);
+        assert_eq!(table.upcase(b'_' as u16), b'_' as u16);
+        assert_eq!(table.upcase('é' as u16), 'É' as u16);
+        assert_eq!(table.upcase('ω' as u16), 'Ω' as u16);
+        // ß upper cases to two characters, so it maps to itself
+        assert_eq!(table.upcase('ß' as u16), 'ß' as u16);
+        assert_eq!(table.upcase('µ' as u16), 'µ' as u16);
+        assert_eq!(table.upcase('ǆ' as u16), 'Ǆ' as u16);
+        assert_eq!(table.upcase('ǅ' as u16), 'ǅ' as u16);
+        assert_eq!(table.upcase(0xD800), 0xD800);
+        assert_eq!(table.upcase(0xFFFF), 0xFFFF);
+
+        let compressed = table.compress();
+        // most code units map to themselves
+        assert!(compressed.len() < 8 * 1024);
+        // formatting writes the same table whichever toolchain built it
+        assert_eq!(compressed.len(), 2852);
+        assert_eq!(table_checksum(&compressed), 0x6948_00E4);
+        assert_eq!(UpcaseTable::decompress(&compressed).unwrap(), table);
+
+        let uncompressed: Vec<u8> = table
+            .mapping
+            .iter()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        assert_eq!(UpcaseTable::decompress(&uncompressed).unwrap(), table);
+
+        assert!(matches!(
+            UpcaseTable::decompress(&[0xFF, 0xFF, 0xFF, 0xFF, 1, 0, 2, 0]),
+            Err(Error::TooLong)
+        ));
+    }
+
+    #[test]
+    pub fn checksum() {
+        assert_eq!(table_checksum(&[]), 0);
+        assert_eq!(table_checksum(&[1]), 1);
+        // 1 rotated right is 80000000h
+        assert_eq!(table_checksum(&[1, 2]), 0x8000_0002);
+        assert_eq!(table_checksum(&[1, 2, 3]), 0x4000_0004);
+    }
+
+    #[test]
+    pub fn compare_names() {
+        let table = UpcaseTable::generate();
+        assert!(
+            table.eq_ignore_case(&utf16("Read Me.txt"), &utf16("READ ME.TXT"))
+        );
+        assert!(table.eq_ignore_case(&utf16("Ünïcödé"), &utf16("ÜNÏCÖDÉ")));
+        assert!(!table.eq_ignore_case(&utf16("a"), &utf16("aa")));
+        assert!(!table.eq_ignore_case(&utf16("straße"), &utf16("STRASSE")));
+        assert_eq!(
+            table.cmp_ignore_case(&utf16("apple"), &utf16("BANANA")),
+            core::cmp::Ordering::Less
+        );
+    }
+}